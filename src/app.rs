@@ -1,31 +1,45 @@
 use std::{
     borrow::Cow,
+    future::Future,
     mem,
     sync::{
         mpsc::{self, Receiver, Sender},
         Arc,
     },
-    time::Duration,
 };
 
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use futures::future::BoxFuture;
-use log::error;
+use log::warn;
 use thiserror::Error;
 use tokio::runtime::Runtime;
 use wgpu::{
-    Adapter, Backends, DeviceDescriptor, Features, Instance, InstanceDescriptor, Limits,
-    RequestAdapterOptions, Surface, SurfaceConfiguration, SurfaceTexture, TextureUsages,
+    Adapter, Backends, DeviceDescriptor, DeviceLostReason, Features, Instance, InstanceDescriptor,
+    Limits, PowerPreference, PresentMode, RequestAdapterOptions, Surface, SurfaceConfiguration,
+    SurfaceError, SurfaceTexture, TextureUsages,
 };
 use wgpu_async::{AsyncDevice, AsyncQueue};
 use winit::{
     application::ApplicationHandler,
     dpi::PhysicalSize,
     event::WindowEvent,
-    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy},
     window::{Window, WindowAttributes},
 };
 
+mod adapter;
+mod gui;
+mod pacing;
+mod split;
+mod surface;
+
+pub use adapter::{AdapterCli, GraphicsBackend, Power};
+use gui::Gui;
+use pacing::FramePacer;
+pub use pacing::{FramePacing, StepBudget};
+pub use split::{Pane, SplitScreen};
+pub use surface::{AlphaMode, Presentation, SurfaceCli, SurfaceOptions};
+
 pub trait AppBuilder: Send + 'static {
     type App: App;
     fn build(
@@ -33,22 +47,49 @@ pub trait AppBuilder: Send + 'static {
         surface_configuration: &SurfaceConfiguration,
         context: Context,
     ) -> BoxFuture<'static, Result<Self::App>>;
+
+    /// Recreates the GPU resources of `app` on a new device, after the one it was built on was
+    /// lost, e.g. to a driver reset.
+    ///
+    /// Fails by default, which stops [`Run::run`].
+    fn rebuild(
+        _app: Self::App,
+        _surface_configuration: &SurfaceConfiguration,
+        _context: Context,
+    ) -> BoxFuture<'static, Result<Self::App>> {
+        Box::pin(async { Err(eyre!("the app can't be moved to a new device")) })
+    }
 }
 
 pub trait App: Send + 'static {
     fn event(&mut self, event: WindowEvent, context: Context, controller: LocalAppController);
 
     fn render(&mut self, target: &SurfaceTexture, context: Context) -> Result<()>;
+
+    /// Builds the GUI drawn over each frame, if enabled with [`Run::with_gui`].
+    ///
+    /// Window events used by the GUI, e.g. clicks on its widgets, aren't passed to
+    /// [`App::event`].
+    fn gui(&mut self, _gui: &egui::Context, _context: Context) {}
+
+    /// Whether the window should keep being redrawn, paced by [`Run::with_frame_pacing`].
+    ///
+    /// Otherwise it is only redrawn after window events, e.g. while the simulation is paused.
+    fn is_animating(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct AppController {
     exit_tx: mpsc::Sender<()>,
+    proxy: EventLoopProxy<()>,
 }
 
 #[derive(Debug, Clone)]
 pub struct LocalAppController<'a> {
     exit_tx: mpsc::Sender<()>,
+    proxy: EventLoopProxy<()>,
     event_loop: &'a ActiveEventLoop,
 }
 
@@ -60,6 +101,7 @@ impl LocalAppController<'_> {
     pub fn into_non_local(self) -> AppController {
         AppController {
             exit_tx: self.exit_tx,
+            proxy: self.proxy,
         }
     }
 
@@ -71,6 +113,8 @@ impl LocalAppController<'_> {
 impl AppController {
     pub fn exit(&self) {
         self.exit_tx.send(()).expect("failed to send exit message");
+        // the event loop may be waiting for events
+        self.proxy.send_event(()).ok();
     }
 }
 
@@ -135,13 +179,143 @@ impl<'a> Context<'a> {
     }
 }
 
+impl Context<'static> {
+    /// Creates a context without a window, e.g. to render offscreen or evolve maps in a batch
+    /// job, running its tasks on `runtime`.
+    pub async fn headless(runtime: Arc<Runtime>, options: ContextOptions) -> Result<Self> {
+        let instance = options.instance();
+        let adapter = options.request_adapter(&instance, None).await?;
+        let (device, queue) = options.request_device(&adapter).await?;
+
+        Ok(Self {
+            inner: Cow::Owned(ContextInner {
+                runtime,
+                instance,
+                adapter,
+                device,
+                queue,
+            }),
+        })
+    }
+}
+
+/// How the adapter and device of a [`Context`] are chosen.
+#[derive(Debug, Clone)]
+pub struct ContextOptions {
+    pub backends: Backends,
+    pub power_preference: PowerPreference,
+    /// Request a software adapter, e.g. on machines without a GPU.
+    pub force_fallback_adapter: bool,
+    pub features: Features,
+    /// Enabled only when supported by the adapter.
+    pub optional_features: Features,
+    pub limits: Limits,
+}
+
+impl Default for ContextOptions {
+    fn default() -> Self {
+        Self {
+            backends: Backends::all(),
+            power_preference: PowerPreference::default(),
+            force_fallback_adapter: false,
+            features: Features::empty(),
+            optional_features: Features::empty(),
+            limits: Limits::default(),
+        }
+    }
+}
+
+impl ContextOptions {
+    pub fn with_backends(mut self, backends: Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    pub fn with_power_preference(mut self, power_preference: PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    pub fn with_force_fallback_adapter(mut self, force_fallback_adapter: bool) -> Self {
+        self.force_fallback_adapter = force_fallback_adapter;
+        self
+    }
+
+    pub fn with_features(mut self, features: Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    pub fn with_optional_features(mut self, features: Features) -> Self {
+        self.optional_features = features;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// All adapters of [`ContextOptions::backends`], whether or not they'd be chosen.
+    pub fn enumerate_adapters(&self) -> Vec<Adapter> {
+        self.instance().enumerate_adapters(self.backends)
+    }
+
+    /// The adapter a headless [`Context`] would be created on.
+    pub async fn adapter(&self) -> Result<Adapter> {
+        self.request_adapter(&self.instance(), None).await
+    }
+
+    fn instance(&self) -> Instance {
+        Instance::new(&InstanceDescriptor {
+            backends: self.backends,
+            ..Default::default()
+        })
+    }
+
+    async fn request_adapter(
+        &self,
+        instance: &Instance,
+        compatible_surface: Option<&Surface<'_>>,
+    ) -> Result<Adapter> {
+        Ok(instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference: self.power_preference,
+                force_fallback_adapter: self.force_fallback_adapter,
+                compatible_surface,
+            })
+            .await
+            .ok_or(NoAdapter)?)
+    }
+
+    async fn request_device(&self, adapter: &Adapter) -> Result<(AsyncDevice, AsyncQueue)> {
+        let (device, queue) = adapter
+            .request_device(
+                &DeviceDescriptor {
+                    label: Some("Device"),
+                    required_features: self.features
+                        | (self.optional_features & adapter.features()),
+                    required_limits: self.limits.clone(),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await
+            .wrap_err_with(|| format!("failed to open {}", adapter.get_info().name))?;
+        Ok(wgpu_async::wrap(Arc::new(device), Arc::new(queue)))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Run<A: AppBuilder> {
     pub app_builder: A,
     pub window_attributes: WindowAttributes,
-    pub features: Features,
-    pub limits: Limits,
+    pub context_options: ContextOptions,
     pub surface_usages: TextureUsages,
+    pub surface_options: SurfaceOptions,
+    /// Whether to draw [`App::gui`].
+    pub gui: bool,
+    pub frame_pacing: FramePacing,
 }
 
 impl<A: AppBuilder> Run<A> {
@@ -149,9 +323,11 @@ impl<A: AppBuilder> Run<A> {
         Self {
             app_builder,
             window_attributes: Default::default(),
-            features: Default::default(),
-            limits: Default::default(),
+            context_options: Default::default(),
             surface_usages: TextureUsages::RENDER_ATTACHMENT,
+            surface_options: SurfaceOptions::default(),
+            gui: false,
+            frame_pacing: FramePacing::default(),
         }
     }
 
@@ -160,13 +336,38 @@ impl<A: AppBuilder> Run<A> {
         self
     }
 
+    pub fn with_context_options(mut self, options: ContextOptions) -> Self {
+        self.context_options = options;
+        self
+    }
+
+    pub fn with_backends(mut self, backends: Backends) -> Self {
+        self.context_options.backends = backends;
+        self
+    }
+
+    pub fn with_power_preference(mut self, power_preference: PowerPreference) -> Self {
+        self.context_options.power_preference = power_preference;
+        self
+    }
+
+    pub fn with_force_fallback_adapter(mut self, force_fallback_adapter: bool) -> Self {
+        self.context_options.force_fallback_adapter = force_fallback_adapter;
+        self
+    }
+
     pub fn with_features(mut self, features: Features) -> Self {
-        self.features = features;
+        self.context_options.features = features;
+        self
+    }
+
+    pub fn with_optional_features(mut self, features: Features) -> Self {
+        self.context_options.optional_features = features;
         self
     }
 
     pub fn with_limits(mut self, limits: Limits) -> Self {
-        self.limits = limits;
+        self.context_options.limits = limits;
         self
     }
 
@@ -175,13 +376,38 @@ impl<A: AppBuilder> Run<A> {
         self
     }
 
+    pub fn with_surface_options(mut self, options: SurfaceOptions) -> Self {
+        self.surface_options = options;
+        self
+    }
+
+    pub fn with_present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.surface_options.present_mode = present_mode;
+        self
+    }
+
+    pub fn with_gui(mut self, gui: bool) -> Self {
+        self.gui = gui;
+        self
+    }
+
+    pub fn with_frame_pacing(mut self, frame_pacing: FramePacing) -> Self {
+        self.frame_pacing = frame_pacing;
+        self
+    }
+
     pub fn run(self) -> Result<()> {
-        let event_loop = EventLoop::new()?;
-        event_loop.set_control_flow(ControlFlow::Poll);
-        Ok(event_loop.run_app(&mut AppContainer::Created(CreatedApp {
+        let event_loop = EventLoop::new().wrap_err("failed to create event loop")?;
+        let mut container = AppContainer::Created(CreatedApp {
             run: self,
-            runtime: Arc::new(Runtime::new()?),
-        }))?)
+            runtime: Arc::new(Runtime::new().wrap_err("failed to start async runtime")?),
+            proxy: event_loop.create_proxy(),
+        });
+        event_loop.run_app(&mut container)?;
+        match container {
+            AppContainer::Failed(error) => Err(error),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -189,13 +415,26 @@ impl<A: AppBuilder> Run<A> {
 enum AppContainer<A: AppBuilder> {
     Error,
     Created(CreatedApp<A>),
+    /// Waiting for the app to be started, or moved to a new device, on the runtime.
+    Pending(PendingApp<A::App>),
     Ready(ReadyAppContainer<A::App>),
+    /// Stopped the event loop, to be returned from [`Run::run`].
+    Failed(Report),
 }
 
 #[derive(Debug)]
 struct CreatedApp<A: AppBuilder> {
     run: Run<A>,
     runtime: Arc<Runtime>,
+    // wakes the event loop once a pending app is ready
+    proxy: EventLoopProxy<()>,
+}
+
+#[derive(Debug)]
+struct PendingApp<A: App> {
+    ready_rx: Receiver<Result<ReadyAppContainer<A>>>,
+    // the GUI is created once ready, on the event loop's thread like the window it draws to
+    gui: bool,
 }
 
 #[derive(Debug)]
@@ -203,15 +442,147 @@ struct WindowSurface {
     window: Arc<Window>,
     surface: Surface<'static>,
     surface_configuration: SurfaceConfiguration,
+    // whether the last frame was skipped, to only warn about the first of a series
+    skipping_frames: bool,
+}
+
+impl WindowSurface {
+    // surfaces can't be configured with a zero size, e.g. while the window is minimised
+    fn has_area(&self) -> bool {
+        self.surface_configuration.width > 0 && self.surface_configuration.height > 0
+    }
+
+    fn configure(&self, device: &AsyncDevice) {
+        if self.has_area() {
+            self.surface.configure(device, &self.surface_configuration);
+        }
+    }
+
+    /// Acquires the next texture to draw to, or `None` if the frame should be skipped.
+    fn current_texture(&mut self, device: &AsyncDevice) -> Result<Option<SurfaceTexture>> {
+        let texture = match self.surface.get_current_texture() {
+            Ok(texture) => Some(texture),
+            Err(SurfaceError::Timeout) => {
+                self.skip_frame("timed out acquiring a surface texture");
+                None
+            }
+            Err(SurfaceError::Outdated | SurfaceError::Lost) => {
+                // usually the window was resized and the event hasn't arrived yet
+                let size = self.window.inner_size();
+                self.surface_configuration.width = size.width;
+                self.surface_configuration.height = size.height;
+                if !self.has_area() {
+                    return Ok(None);
+                }
+                self.configure(device);
+                match self.surface.get_current_texture() {
+                    Ok(texture) => Some(texture),
+                    Err(
+                        error @ (SurfaceError::Outdated
+                        | SurfaceError::Lost
+                        | SurfaceError::Timeout),
+                    ) => {
+                        self.skip_frame(&format!(
+                            "no surface texture after reconfiguring ({error})"
+                        ));
+                        None
+                    }
+                    Err(error) => {
+                        return Err(error)
+                            .wrap_err("failed to acquire surface texture after reconfiguring it")
+                    }
+                }
+            }
+            Err(error) => return Err(error).wrap_err("failed to acquire surface texture"),
+        };
+        if texture.is_some() {
+            self.skipping_frames = false;
+        }
+        Ok(texture)
+    }
+
+    fn skip_frame(&mut self, reason: &str) {
+        if !self.skipping_frames {
+            warn!("{reason}, skipping frames until a surface texture can be acquired");
+        }
+        self.skipping_frames = true;
+    }
+
+    fn gui(&self, context: Context) -> Gui {
+        // egui encodes its colours to sRGB itself, so it draws to a linear view if there is one
+        let format = self
+            .surface_configuration
+            .view_formats
+            .first()
+            .copied()
+            .unwrap_or(self.surface_configuration.format);
+        if format.is_srgb() {
+            warn!("the GUI is drawn to an sRGB surface without a linear view, colours will be off");
+        }
+        Gui::new(&self.window, format, context)
+    }
+}
+
+/// What a window needs to open a device again after losing one.
+#[derive(Debug)]
+struct DeviceOptions {
+    context: ContextOptions,
+    surface: SurfaceOptions,
+    surface_usages: TextureUsages,
+    gui: bool,
+    lost_tx: Sender<String>,
+}
+
+impl DeviceOptions {
+    /// Opens a device that can present to `surface`, returning how to configure it.
+    async fn open(
+        &self,
+        instance: &Instance,
+        surface: &Surface<'_>,
+        size: PhysicalSize<u32>,
+    ) -> Result<(Adapter, AsyncDevice, AsyncQueue, SurfaceConfiguration)> {
+        let adapter = self
+            .context
+            .request_adapter(instance, Some(surface))
+            .await?;
+        let (device, queue) = self.context.request_device(&adapter).await?;
+
+        let lost_tx = self.lost_tx.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // also called when the device is dropped
+            if reason != DeviceLostReason::Destroyed {
+                lost_tx.send(message).ok();
+            }
+        });
+
+        let surface_configuration = self
+            .surface
+            .configuration(
+                &surface.get_capabilities(&adapter),
+                adapter.get_downlevel_capabilities().flags,
+                self.gui,
+                self.surface_usages,
+                size,
+            )
+            .wrap_err_with(|| {
+                format!("failed to configure surface on {}", adapter.get_info().name)
+            })?;
+        Ok((adapter, device, queue, surface_configuration))
+    }
 }
 
 #[derive(Debug)]
 struct ReadyAppContainer<A: App> {
     context: ContextInner,
     window: WindowSurface,
+    gui: Option<Gui>,
+    pacer: FramePacer,
     app: A,
     exit_tx: Sender<()>,
     exit_rx: Receiver<()>,
+    device_options: DeviceOptions,
+    device_lost_rx: Receiver<String>,
+    proxy: EventLoopProxy<()>,
 }
 
 impl<A: AppBuilder> ApplicationHandler for AppContainer<A> {
@@ -220,39 +591,40 @@ impl<A: AppBuilder> ApplicationHandler for AppContainer<A> {
             return;
         }
 
-        let Self::Created(CreatedApp { run, runtime }) = mem::replace(self, Self::Error) else {
+        let Self::Created(created) = mem::replace(self, Self::Error) else {
             unreachable!()
         };
 
-        let instance = Instance::new(&InstanceDescriptor {
-            backends: Backends::all(),
-            ..Default::default()
-        });
-
-        let window = match event_loop.create_window(run.window_attributes.clone()) {
+        *self = match created.start(event_loop) {
+            Ok(pending) => pending,
             Err(error) => {
-                error!("failed to create window: {error}");
                 event_loop.exit();
-                return;
+                Self::Failed(error)
             }
-            Ok(window) => Arc::new(window),
         };
+    }
 
-        let surface = match instance.create_surface(window.clone()) {
+    // sent once a pending app is ready
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, (): ()) {
+        let Self::Pending(pending) = self else {
+            return;
+        };
+        let Ok(ready) = pending.ready_rx.try_recv() else {
+            return;
+        };
+        let gui = pending.gui;
+
+        *self = match ready {
+            Ok(mut ready) => {
+                ready.gui = gui.then(|| ready.window.gui(Context::borrowed(&ready.context)));
+                ready.pacer.request();
+                Self::Ready(ready)
+            }
             Err(error) => {
-                error!("failed to create surface: {error}");
                 event_loop.exit();
-                return;
+                Self::Failed(error)
             }
-            Ok(surface) => surface,
         };
-
-        *self = AppContainer::Ready(
-            futures::executor::block_on(ReadyAppContainer::new(
-                run, instance, surface, window, runtime,
-            ))
-            .expect("failed to create app"),
-        );
     }
 
     fn window_event(
@@ -261,12 +633,19 @@ impl<A: AppBuilder> ApplicationHandler for AppContainer<A> {
         _: winit::window::WindowId,
         event: WindowEvent,
     ) {
+        if !self.recover_lost_device() {
+            return;
+        }
         let Self::Ready(ReadyAppContainer {
             context,
             window,
+            gui,
+            pacer,
             app,
             exit_tx,
             exit_rx,
+            proxy,
+            ..
         }) = self
         else {
             return;
@@ -278,58 +657,170 @@ impl<A: AppBuilder> ApplicationHandler for AppContainer<A> {
         }
 
         let context = Context::borrowed(context);
+        let used_by_gui = gui
+            .as_mut()
+            .is_some_and(|gui| gui.event(&window.window, &event));
 
         match event {
             WindowEvent::Resized(new_size) => {
                 window.surface_configuration.width = new_size.width;
                 window.surface_configuration.height = new_size.height;
-                window
-                    .surface
-                    .configure(context.device(), &window.surface_configuration);
+                window.configure(context.device());
             }
 
             WindowEvent::RedrawRequested => {
-                let surface = match window.surface.get_current_texture() {
+                pacer.frame();
+                if !window.has_area() {
+                    return;
+                }
+                let surface = match window.current_texture(context.device()) {
                     Err(error) => {
-                        error!("failed to acquire Surface current texture: {error}");
                         event_loop.exit();
+                        *self = Self::Failed(error);
                         return;
                     }
-                    Ok(surface) => surface,
-                };
-                window.window.pre_present_notify();
-                match app.render(&surface, context.borrow()) {
-                    Err(error) => {
-                        error!("failed to render app: {error}");
-                        event_loop.exit();
+                    Ok(None) => {
+                        pacer.retry();
                         return;
                     }
-                    Ok(_) => {
-                        surface.present();
-                    }
+                    Ok(Some(surface)) => surface,
+                };
+                window.window.pre_present_notify();
+                if let Err(error) = app.render(&surface, context.borrow()) {
+                    event_loop.exit();
+                    *self = Self::Failed(error.wrap_err("failed to render app"));
+                    return;
+                }
+                if let Some(gui) = gui {
+                    gui.render(&window.window, &surface, app, context.borrow());
+                }
+                let suboptimal = surface.suboptimal;
+                surface.present();
+                if suboptimal {
+                    window.configure(context.device());
                 }
 
-                let window = window.window.clone();
-                context.runtime().spawn(async move {
-                    tokio::time::sleep(Duration::from_millis(200)).await;
-                    window.request_redraw();
-                });
+                if app.is_animating() {
+                    pacer.request();
+                }
             }
 
-            _ => {}
+            // e.g. the view may have moved
+            _ => pacer.request(),
         }
 
-        let exit_tx = exit_tx.clone();
+        if used_by_gui {
+            return;
+        }
         let controller = LocalAppController {
-            exit_tx,
+            exit_tx: exit_tx.clone(),
+            proxy: proxy.clone(),
             event_loop,
         };
         app.event(event, context, controller);
     }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if !self.recover_lost_device() {
+            // a pending app wakes the event loop up once ready
+            event_loop.set_control_flow(ControlFlow::Wait);
+            return;
+        }
+        let Self::Ready(ReadyAppContainer {
+            window,
+            pacer,
+            exit_rx,
+            ..
+        }) = self
+        else {
+            return;
+        };
+
+        if exit_rx.try_recv().is_ok() {
+            event_loop.exit();
+            return;
+        }
+        pacer.about_to_wait(&window.window, event_loop);
+    }
+}
+
+impl<A: AppBuilder> AppContainer<A> {
+    /// Drives `ready` on `runtime`, without blocking the event loop, which gets the app back in
+    /// [`ApplicationHandler::user_event`].
+    fn pending(
+        runtime: &Runtime,
+        proxy: EventLoopProxy<()>,
+        gui: bool,
+        ready: impl Future<Output = Result<ReadyAppContainer<A::App>>> + Send + 'static,
+    ) -> Self {
+        let (ready_tx, ready_rx) = mpsc::channel();
+        runtime.spawn(async move {
+            ready_tx.send(ready.await).ok();
+            proxy.send_event(()).ok();
+        });
+        Self::Pending(PendingApp { ready_rx, gui })
+    }
+
+    /// Starts moving the app to a new device if its device was lost, returning whether it is
+    /// ready to handle events.
+    fn recover_lost_device(&mut self) -> bool {
+        let Self::Ready(ready) = self else {
+            return false;
+        };
+        let Some(message) = ready.device_lost_rx.try_iter().last() else {
+            return true;
+        };
+
+        let Self::Ready(mut ready) = mem::replace(self, Self::Error) else {
+            unreachable!()
+        };
+        // its textures were on the old device too
+        let gui = ready.gui.take().is_some();
+        let runtime = ready.context.runtime.clone();
+        let proxy = ready.proxy.clone();
+        *self = Self::pending(&runtime, proxy, gui, async move {
+            ready
+                .recover::<A>(message)
+                .await
+                .wrap_err("failed to recover from losing the device")
+        });
+        false
+    }
+}
+
+impl<A: AppBuilder> CreatedApp<A> {
+    fn start(self, event_loop: &ActiveEventLoop) -> Result<AppContainer<A>> {
+        let Self {
+            run,
+            runtime,
+            proxy,
+        } = self;
+        let instance = run.context_options.instance();
+        let window = Arc::new(
+            event_loop
+                .create_window(run.window_attributes.clone())
+                .wrap_err("failed to create window")?,
+        );
+        let surface = instance
+            .create_surface(window.clone())
+            .wrap_err("failed to create surface")?;
+        let gui = run.gui;
+        let ready = ReadyAppContainer::new(
+            run,
+            instance,
+            surface,
+            window,
+            runtime.clone(),
+            proxy.clone(),
+        );
+        Ok(AppContainer::pending(&runtime, proxy, gui, async move {
+            ready.await.wrap_err("failed to create app")
+        }))
+    }
 }
 
 #[derive(Debug, Clone, Copy, Error)]
-#[error("no compatible adapter")]
+#[error("no compatible graphics adapter found")]
 pub struct NoAdapter;
 
 #[derive(Debug, Clone, Copy, Error)]
@@ -343,41 +834,26 @@ impl<A: App> ReadyAppContainer<A> {
         surface: Surface<'static>,
         window: Arc<Window>,
         runtime: Arc<Runtime>,
+        proxy: EventLoopProxy<()>,
     ) -> Result<Self> {
-        let adapter = instance
-            .request_adapter(&RequestAdapterOptions {
-                compatible_surface: Some(&surface),
-                ..Default::default()
-            })
-            .await
-            .ok_or(NoAdapter)?;
-
-        let (device, queue) = adapter
-            .request_device(
-                &DeviceDescriptor {
-                    label: Some("Device"),
-                    required_features: run.features,
-                    required_limits: run.limits,
-                    ..Default::default()
-                },
-                None,
-            )
+        let (device_lost_tx, device_lost_rx) = mpsc::channel();
+        let device_options = DeviceOptions {
+            context: run.context_options,
+            surface: run.surface_options,
+            surface_usages: run.surface_usages,
+            gui: run.gui,
+            lost_tx: device_lost_tx,
+        };
+        let (adapter, device, queue, surface_configuration) = device_options
+            .open(&instance, &surface, window.inner_size())
             .await?;
-        let (device, queue) = wgpu_async::wrap(Arc::new(device), Arc::new(queue));
-
-        let surface_capabilities = surface.get_capabilities(&adapter);
-        let PhysicalSize { width, height } = window.inner_size();
-        let surface_configuration = SurfaceConfiguration {
-            usage: TextureUsages::RENDER_ATTACHMENT | run.surface_usages,
-            format: surface_capabilities.formats[0],
-            width,
-            height,
-            present_mode: surface_capabilities.present_modes[0],
-            alpha_mode: surface_capabilities.alpha_modes[0],
-            desired_maximum_frame_latency: 2,
-            view_formats: vec![],
+        let window = WindowSurface {
+            window,
+            surface,
+            surface_configuration,
+            skipping_frames: false,
         };
-        surface.configure(&device, &surface_configuration);
+        window.configure(&device);
 
         let context = ContextInner {
             runtime,
@@ -389,21 +865,76 @@ impl<A: App> ReadyAppContainer<A> {
 
         let app = run
             .app_builder
-            .build(&surface_configuration, Context::borrowed(&context))
+            .build(&window.surface_configuration, Context::borrowed(&context))
             .await?;
 
         let (exit_tx, exit_rx) = mpsc::channel();
 
         Ok(Self {
             context,
-            window: WindowSurface {
-                window,
-                surface,
-                surface_configuration,
-            },
+            // see `PendingApp`
+            gui: None,
+            pacer: FramePacer::new(run.frame_pacing),
+            window,
+            app,
+            exit_tx,
+            exit_rx,
+            device_options,
+            device_lost_rx,
+            proxy,
+        })
+    }
+
+    /// Opens a new device after losing the old one, and rebuilds everything on it.
+    async fn recover<B: AppBuilder<App = A>>(self, message: String) -> Result<Self> {
+        warn!("lost the device ({message}), opening a new one");
+        let Self {
+            context,
+            mut window,
+            gui,
+            pacer,
+            app,
+            exit_tx,
+            exit_rx,
+            device_options,
+            device_lost_rx,
+            proxy,
+        } = self;
+
+        let ContextInner {
+            runtime, instance, ..
+        } = context;
+        let (adapter, device, queue, surface_configuration) = device_options
+            .open(&instance, &window.surface, window.window.inner_size())
+            .await?;
+        window.surface_configuration = surface_configuration;
+        window.configure(&device);
+
+        let context = ContextInner {
+            runtime,
+            instance,
+            adapter,
+            device,
+            queue,
+        };
+        let app = B::rebuild(
+            app,
+            &window.surface_configuration,
+            Context::borrowed(&context),
+        )
+        .await?;
+
+        Ok(Self {
+            context,
+            window,
+            gui,
+            pacer,
             app,
             exit_tx,
             exit_rx,
+            device_options,
+            device_lost_rx,
+            proxy,
         })
     }
 }
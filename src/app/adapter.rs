@@ -0,0 +1,82 @@
+use clap::{Args, ValueEnum};
+use wgpu::{Backends, PowerPreference};
+
+use super::ContextOptions;
+
+/// A graphics API that adapters can be requested through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GraphicsBackend {
+    Vulkan,
+    Metal,
+    Dx12,
+    /// OpenGL, or WebGL on the web.
+    Gl,
+    #[value(name = "webgpu")]
+    BrowserWebGpu,
+}
+
+impl From<GraphicsBackend> for Backends {
+    fn from(backend: GraphicsBackend) -> Self {
+        match backend {
+            GraphicsBackend::Vulkan => Self::VULKAN,
+            GraphicsBackend::Metal => Self::METAL,
+            GraphicsBackend::Dx12 => Self::DX12,
+            GraphicsBackend::Gl => Self::GL,
+            GraphicsBackend::BrowserWebGpu => Self::BROWSER_WEBGPU,
+        }
+    }
+}
+
+/// Which kind of GPU to prefer when there are several.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Power {
+    /// Let the driver decide.
+    #[default]
+    Default,
+    /// Usually an integrated GPU.
+    Low,
+    /// Usually a discrete GPU.
+    High,
+}
+
+impl From<Power> for PowerPreference {
+    fn from(power: Power) -> Self {
+        match power {
+            Power::Default => Self::None,
+            Power::Low => Self::LowPower,
+            Power::High => Self::HighPerformance,
+        }
+    }
+}
+
+/// Command line flags choosing the adapter, see [`ContextOptions`].
+#[derive(Debug, Clone, Default, Args)]
+pub struct AdapterCli {
+    /// Only consider adapters of these graphics APIs, all by default. Run `adapters` to list them.
+    #[arg(long = "backend", value_enum, value_delimiter = ',')]
+    pub backends: Vec<GraphicsBackend>,
+
+    #[arg(long, value_enum, default_value_t)]
+    pub power: Power,
+
+    /// Use a software adapter, e.g. on machines without a GPU.
+    #[arg(long)]
+    pub fallback_adapter: bool,
+}
+
+impl AdapterCli {
+    pub fn apply(&self, options: ContextOptions) -> ContextOptions {
+        let backends = match self.backends.as_slice() {
+            [] => options.backends,
+            backends => backends
+                .iter()
+                .copied()
+                .map(Backends::from)
+                .fold(Backends::empty(), |all, backend| all | backend),
+        };
+        options
+            .with_backends(backends)
+            .with_power_preference(self.power.into())
+            .with_force_fallback_adapter(self.fallback_adapter)
+    }
+}
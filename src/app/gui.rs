@@ -0,0 +1,132 @@
+use std::{fmt, iter};
+
+use egui_wgpu::ScreenDescriptor;
+use wgpu::{
+    CommandEncoderDescriptor, LoadOp, Operations, RenderPassColorAttachment, RenderPassDescriptor,
+    StoreOp, SurfaceTexture, TextureFormat, TextureViewDescriptor,
+};
+use winit::{event::WindowEvent, window::Window};
+
+use super::{App, Context};
+use crate::util::SyncingFuture;
+
+/// Draws the immediate mode GUI of an [`App`] over what it rendered, see [`App::gui`].
+pub(super) struct Gui {
+    context: egui::Context,
+    state: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+    // of the view of the target, which may differ from the target's in sRGB encoding
+    texture_format: TextureFormat,
+}
+
+impl fmt::Debug for Gui {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Gui").finish_non_exhaustive()
+    }
+}
+
+impl Gui {
+    pub(super) fn new(window: &Window, texture_format: TextureFormat, context: Context) -> Self {
+        let egui_context = egui::Context::default();
+        let state = egui_winit::State::new(
+            egui_context.clone(),
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            window.theme(),
+            Some(context.device().limits().max_texture_dimension_2d as usize),
+        );
+        let renderer = egui_wgpu::Renderer::new(context.device(), texture_format, None, 1, false);
+
+        Self {
+            context: egui_context,
+            state,
+            renderer,
+            texture_format,
+        }
+    }
+
+    /// Returns whether the GUI used the event, in which case the app shouldn't.
+    pub(super) fn event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.state.on_window_event(window, event).consumed
+    }
+
+    pub(super) fn render(
+        &mut self,
+        window: &Window,
+        target: &SurfaceTexture,
+        app: &mut impl App,
+        context: Context,
+    ) {
+        let input = self.state.take_egui_input(window);
+        let output = self
+            .context
+            .run(input, |gui| app.gui(gui, context.borrow()));
+        self.state
+            .handle_platform_output(window, output.platform_output);
+
+        let paint_jobs = self
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: [target.texture.width(), target.texture.height()],
+            pixels_per_point: output.pixels_per_point,
+        };
+
+        for (id, image_delta) in &output.textures_delta.set {
+            self.renderer
+                .update_texture(context.device(), context.queue(), *id, image_delta);
+        }
+
+        let mut encoder = context
+            .device()
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("GUI Command Encoder"),
+            });
+        let buffer_commands = self.renderer.update_buffers(
+            context.device(),
+            context.queue(),
+            &mut encoder,
+            &paint_jobs,
+            &screen_descriptor,
+        );
+
+        let view = target.texture.create_view(&TextureViewDescriptor {
+            label: Some("GUI Texture View"),
+            format: Some(self.texture_format),
+            ..Default::default()
+        });
+        {
+            let mut render_pass = encoder
+                .begin_render_pass(&RenderPassDescriptor {
+                    label: Some("GUI Render Pass"),
+                    color_attachments: &[Some(RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: Operations {
+                            load: LoadOp::Load,
+                            store: StoreOp::Store,
+                        },
+                    })],
+                    ..Default::default()
+                })
+                .forget_lifetime();
+            self.renderer
+                .render(&mut render_pass, &paint_jobs, &screen_descriptor);
+        }
+
+        // drawn over what the app submitted before
+        context
+            .queue()
+            .submit(
+                buffer_commands
+                    .into_iter()
+                    .chain(iter::once(encoder.finish())),
+            )
+            .ignore();
+
+        for id in &output.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
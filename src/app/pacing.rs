@@ -0,0 +1,147 @@
+use std::{
+    num::NonZero,
+    sync::atomic::{AtomicU32, Ordering},
+    time::{Duration, Instant},
+};
+
+use winit::{
+    event_loop::{ActiveEventLoop, ControlFlow},
+    window::Window,
+};
+
+// how long to wait before redrawing after a skipped frame, e.g. while the surface stays outdated
+const SKIPPED_FRAME_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How often a window is redrawn while its [`super::App`] is animating.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum FramePacing {
+    /// As often as presenting allows, which is as often as the display refreshes with
+    /// [`wgpu::PresentMode::Fifo`].
+    #[default]
+    Vsync,
+    /// At most this many frames per second.
+    MaxFps(f32),
+}
+
+impl FramePacing {
+    /// Parses the argument of [`FramePacing::MaxFps`], e.g. as a clap `value_parser`.
+    pub fn parse_max_fps(arg: &str) -> Result<f32, String> {
+        let fps = arg.parse::<f32>().map_err(|err| err.to_string())?;
+        if !fps.is_finite() || fps <= 0.0 || Duration::try_from_secs_f32(fps.recip()).is_err() {
+            return Err(format!(
+                "expected a positive number of frames per second, got {fps}"
+            ));
+        }
+        Ok(fps)
+    }
+
+    fn min_frame_time(self) -> Option<Duration> {
+        match self {
+            Self::Vsync => None,
+            // not limited at all rather than panicking for rates `parse_max_fps` rejects
+            Self::MaxFps(fps) => Duration::try_from_secs_f32(fps.recip()).ok(),
+        }
+    }
+}
+
+/// Schedules redraws: right away after events, then continuously while animating, no more often
+/// than the [`FramePacing`] allows.
+#[derive(Debug)]
+pub(super) struct FramePacer {
+    pacing: FramePacing,
+    last_frame: Option<Instant>,
+    // a redraw is wanted, but not before this
+    pending: Option<Instant>,
+}
+
+impl FramePacer {
+    pub(super) fn new(pacing: FramePacing) -> Self {
+        Self {
+            pacing,
+            last_frame: None,
+            pending: None,
+        }
+    }
+
+    /// Asks for a redraw as soon as the pacing allows.
+    pub(super) fn request(&mut self) {
+        let earliest = self
+            .last_frame
+            .zip(self.pacing.min_frame_time())
+            .map_or_else(Instant::now, |(last_frame, frame_time)| {
+                last_frame + frame_time
+            });
+        self.pending = Some(
+            self.pending
+                .map_or(earliest, |pending| pending.min(earliest)),
+        );
+    }
+
+    /// Asks for a redraw after a frame was skipped, not right away so that a window that can't
+    /// be drawn to doesn't keep the event loop busy. Events may still redraw it sooner.
+    pub(super) fn retry(&mut self) {
+        self.pending = Some(Instant::now() + SKIPPED_FRAME_RETRY_DELAY);
+    }
+
+    /// Call when a frame starts rendering.
+    pub(super) fn frame(&mut self) {
+        self.last_frame = Some(Instant::now());
+        self.pending = None;
+    }
+
+    /// Call once the event loop runs out of events, to redraw or sleep until the next frame.
+    pub(super) fn about_to_wait(&mut self, window: &Window, event_loop: &ActiveEventLoop) {
+        match self.pending {
+            Some(pending) if pending <= Instant::now() => {
+                // with vsync, presenting blocks until the display is ready for the next frame
+                window.request_redraw();
+                event_loop.set_control_flow(ControlFlow::Wait);
+            }
+            Some(pending) => event_loop.set_control_flow(ControlFlow::WaitUntil(pending)),
+            None => event_loop.set_control_flow(ControlFlow::Wait),
+        }
+    }
+}
+
+/// Caps how many simulation steps may be taken per rendered frame, so that a short step interval
+/// can't outrun what is shown.
+///
+/// The render loop calls [`StepBudget::refill`] every frame and the simulation task calls
+/// [`StepBudget::take`] before every step.
+#[derive(Debug)]
+pub struct StepBudget {
+    steps_per_frame: Option<NonZero<u32>>,
+    remaining: AtomicU32,
+}
+
+impl StepBudget {
+    /// No cap if `steps_per_frame` is `None`.
+    pub fn new(steps_per_frame: Option<NonZero<u32>>) -> Self {
+        Self {
+            steps_per_frame,
+            remaining: AtomicU32::new(steps_per_frame.map_or(0, NonZero::get)),
+        }
+    }
+
+    pub fn steps_per_frame(&self) -> Option<NonZero<u32>> {
+        self.steps_per_frame
+    }
+
+    pub fn refill(&self) {
+        if let Some(steps_per_frame) = self.steps_per_frame {
+            self.remaining
+                .store(steps_per_frame.get(), Ordering::Relaxed);
+        }
+    }
+
+    /// Returns whether a step may be taken, using it up.
+    pub fn take(&self) -> bool {
+        self.steps_per_frame.is_none()
+            || self
+                .remaining
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| {
+                    remaining.checked_sub(1)
+                })
+                .is_ok()
+    }
+}
@@ -0,0 +1,246 @@
+use std::sync::{Arc, Mutex};
+
+use egui::{Area, Color32, Frame, Id, Order};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::WindowEvent,
+};
+
+use super::Context;
+use crate::{
+    buffer::Buffer,
+    camera::CameraController,
+    render::{Camera, RenderTarget, Renderer, Viewport},
+    sim::{Point, Simulation},
+};
+
+/// One of the viewports of a [`SplitScreen`], with its own simulation and camera.
+#[derive(Debug)]
+pub struct Pane {
+    pub label: String,
+    pub simulation: Arc<Mutex<Simulation<Buffer<Point>>>>,
+    pub camera: Camera,
+    pub camera_controller: CameraController,
+}
+
+impl Pane {
+    pub fn new(
+        label: impl Into<String>,
+        simulation: Arc<Mutex<Simulation<Buffer<Point>>>>,
+        camera_controller: CameraController,
+        context: Context,
+    ) -> Self {
+        let camera = Camera::new(camera_controller.transform().as_affine2(), context);
+        Self {
+            label: label.into(),
+            simulation,
+            camera,
+            camera_controller,
+        }
+    }
+}
+
+/// Lays out [`Pane`]s in a grid over the window, e.g. to compare map sets side by side.
+///
+/// Mouse input goes to the pane under the cursor and keyboard input to the pane the cursor was
+/// last over. With linked cameras, moving the view of one pane moves all the others along.
+#[derive(Debug)]
+pub struct SplitScreen {
+    panes: Vec<Pane>,
+    size: PhysicalSize<u32>,
+    linked: bool,
+    focus: usize,
+}
+
+impl SplitScreen {
+    /// The camera controllers of `panes` should start out sized like [`SplitScreen::viewports`].
+    pub fn new(panes: Vec<Pane>, size: PhysicalSize<u32>) -> Self {
+        Self {
+            panes,
+            size,
+            linked: false,
+            focus: 0,
+        }
+    }
+
+    pub fn with_linked(mut self, linked: bool) -> Self {
+        self.linked = linked;
+        self
+    }
+
+    pub fn panes(&self) -> &[Pane] {
+        &self.panes
+    }
+
+    pub fn is_linked(&self) -> bool {
+        self.linked
+    }
+
+    /// Links the cameras to that of the focused pane, or unlinks them.
+    pub fn set_linked(&mut self, linked: bool, context: Context) {
+        self.linked = linked;
+        if linked {
+            self.sync_cameras();
+            self.update_cameras(context);
+        }
+    }
+
+    /// The pane that keyboard input goes to.
+    pub fn focused(&self) -> &Pane {
+        &self.panes[self.focus]
+    }
+
+    /// Number of columns and rows of the grid, as close to square as possible.
+    pub fn grid(n_panes: usize) -> (u32, u32) {
+        let n_panes = n_panes.max(1) as u32;
+        let columns = f64::from(n_panes).sqrt().ceil() as u32;
+        (columns, n_panes.div_ceil(columns))
+    }
+
+    /// The viewport of each pane in a window of `size`, row by row from the top left.
+    pub fn viewports_for(n_panes: usize, size: PhysicalSize<u32>) -> Vec<Viewport> {
+        let (columns, rows) = Self::grid(n_panes);
+        let width = size.width / columns;
+        let height = size.height / rows;
+        (0..n_panes as u32)
+            .map(|idx| Viewport {
+                x: idx % columns * width,
+                y: idx / columns * height,
+                width,
+                height,
+            })
+            .collect()
+    }
+
+    pub fn viewports(&self) -> Vec<Viewport> {
+        Self::viewports_for(self.panes.len(), self.size)
+    }
+
+    /// Draws the label of each pane into the top left corner of its viewport, e.g. from
+    /// [`super::App::gui`].
+    pub fn draw_labels(&self, gui: &egui::Context) {
+        let pixels_per_point = gui.pixels_per_point();
+        for (idx, (pane, viewport)) in self.panes.iter().zip(self.viewports()).enumerate() {
+            let corner = egui::pos2(viewport.x as f32, viewport.y as f32) / pixels_per_point;
+            Area::new(Id::new(("Pane Label", idx)))
+                .order(Order::Background)
+                .fixed_pos(corner + egui::vec2(8.0, 8.0))
+                .interactable(false)
+                .show(gui, |ui| {
+                    Frame::NONE
+                        .fill(Color32::from_black_alpha(160))
+                        .inner_margin(4.0)
+                        .show(ui, |ui| ui.label(&pane.label));
+                });
+        }
+    }
+
+    /// Updates the views of the panes, returning whether any of them changed.
+    pub fn event(&mut self, event: &WindowEvent, context: Context) -> bool {
+        let viewports = self.viewports();
+        let changed = match event {
+            WindowEvent::Resized(size) => {
+                self.size = *size;
+                let cell = self.viewports().first().map_or(*size, |viewport| {
+                    PhysicalSize::new(viewport.width, viewport.height)
+                });
+                for pane in &mut self.panes {
+                    pane.camera_controller.event(&WindowEvent::Resized(cell));
+                }
+                true
+            }
+
+            WindowEvent::CursorMoved {
+                device_id,
+                position,
+            } => {
+                // drags stay with the pane they started in
+                let dragging = self.focused().camera_controller.is_dragging();
+                let hovered = viewports
+                    .iter()
+                    .position(|viewport| viewport.contains(position.x, position.y));
+                match hovered {
+                    Some(hovered) if !dragging && hovered != self.focus => {
+                        self.panes[self.focus]
+                            .camera_controller
+                            .event(&WindowEvent::CursorLeft {
+                                device_id: *device_id,
+                            });
+                        self.focus = hovered;
+                    }
+                    _ => {}
+                }
+
+                let viewport = viewports[self.focus];
+                let position = PhysicalPosition::new(
+                    position.x - f64::from(viewport.x),
+                    position.y - f64::from(viewport.y),
+                );
+                self.panes[self.focus]
+                    .camera_controller
+                    .event(&WindowEvent::CursorMoved {
+                        device_id: *device_id,
+                        position,
+                    })
+            }
+
+            WindowEvent::CursorLeft { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::MouseWheel { .. }
+            | WindowEvent::KeyboardInput { .. } => {
+                self.panes[self.focus].camera_controller.event(event)
+            }
+
+            _ => false,
+        };
+
+        if changed {
+            if self.linked {
+                self.sync_cameras();
+            }
+            self.update_cameras(context);
+        }
+        changed
+    }
+
+    // all panes are the same size, so the same transform shows the same view in each
+    fn sync_cameras(&mut self) {
+        let transform = self.focused().camera_controller.transform();
+        for pane in &mut self.panes {
+            pane.camera_controller.set_transform(transform);
+        }
+    }
+
+    fn update_cameras(&self, context: Context) {
+        for pane in &self.panes {
+            pane.camera.set_transform(
+                pane.camera_controller.transform().as_affine2(),
+                context.borrow(),
+            );
+        }
+    }
+
+    pub fn render<T: RenderTarget>(
+        &self,
+        renderer: &Renderer,
+        target: &T,
+        context: Context,
+    ) -> wgpu_async::WgpuFuture<()> {
+        let simulations = self
+            .panes
+            .iter()
+            .map(|pane| pane.simulation.lock().expect("failed to lock mutex"))
+            .collect::<Vec<_>>();
+        renderer.render_viewports(
+            simulations
+                .iter()
+                .zip(&self.panes)
+                .zip(self.viewports())
+                .map(|((simulation, pane), viewport)| {
+                    (simulation.points(), &pane.camera, viewport)
+                }),
+            target,
+            context,
+        )
+    }
+}
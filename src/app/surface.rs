@@ -0,0 +1,202 @@
+use clap::{Args, ValueEnum};
+use color_eyre::eyre::{eyre, Result};
+use log::{info, warn};
+use wgpu::{
+    CompositeAlphaMode, DownlevelFlags, PresentMode, SurfaceCapabilities, SurfaceConfiguration,
+    TextureUsages,
+};
+use winit::dpi::PhysicalSize;
+
+/// Preferences for how a window is presented to, falling back to what the adapter supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SurfaceOptions {
+    /// Prefer a format that encodes linear colours to sRGB when written, see
+    /// [`wgpu::TextureFormat::is_srgb`]. The renderer converts colours to match either way.
+    pub srgb: bool,
+    pub present_mode: PresentMode,
+    pub alpha_mode: CompositeAlphaMode,
+}
+
+impl Default for SurfaceOptions {
+    fn default() -> Self {
+        Self {
+            srgb: true,
+            present_mode: PresentMode::Fifo,
+            alpha_mode: CompositeAlphaMode::Auto,
+        }
+    }
+}
+
+impl SurfaceOptions {
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    pub fn with_present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    pub fn with_alpha_mode(mut self, alpha_mode: CompositeAlphaMode) -> Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    /// With `gui`, the surface also needs a view without sRGB encoding for egui if it can have one.
+    pub(super) fn configuration(
+        &self,
+        capabilities: &SurfaceCapabilities,
+        downlevel_flags: DownlevelFlags,
+        gui: bool,
+        usage: TextureUsages,
+        size: PhysicalSize<u32>,
+    ) -> Result<SurfaceConfiguration> {
+        let view_formats = downlevel_flags.contains(DownlevelFlags::SURFACE_VIEW_FORMATS);
+        let srgb = if self.srgb && gui && !view_formats {
+            info!("the adapter can't view sRGB surfaces as linear for the GUI, preferring linear");
+            false
+        } else {
+            self.srgb
+        };
+        let format = match capabilities
+            .formats
+            .iter()
+            .find(|format| format.is_srgb() == srgb)
+        {
+            Some(&format) => format,
+            None => {
+                let &format = capabilities
+                    .formats
+                    .first()
+                    .ok_or_else(|| eyre!("the adapter can't present to the window"))?;
+                info!(
+                    "no {} surface format, falling back to {format:?}",
+                    if srgb { "sRGB" } else { "linear" }
+                );
+                format
+            }
+        };
+
+        // Fifo is always supported
+        let present_mode = if self.present_mode == PresentMode::Fifo
+            || capabilities.present_modes.contains(&self.present_mode)
+        {
+            self.present_mode
+        } else {
+            warn!(
+                "present mode {:?} isn't supported, falling back to Fifo",
+                self.present_mode
+            );
+            PresentMode::Fifo
+        };
+
+        let alpha_mode = match capabilities.alpha_modes.first() {
+            Some(&fallback)
+                if self.alpha_mode != CompositeAlphaMode::Auto
+                    && !capabilities.alpha_modes.contains(&self.alpha_mode) =>
+            {
+                warn!(
+                    "alpha mode {:?} isn't supported, falling back to {fallback:?}",
+                    self.alpha_mode
+                );
+                fallback
+            }
+            _ => self.alpha_mode,
+        };
+
+        Ok(SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT | usage,
+            format,
+            width: size.width,
+            height: size.height,
+            present_mode,
+            alpha_mode,
+            desired_maximum_frame_latency: 2,
+            // for the GUI, which does its own sRGB encoding
+            view_formats: if gui && view_formats && format.is_srgb() {
+                vec![format.remove_srgb_suffix()]
+            } else {
+                vec![]
+            },
+        })
+    }
+}
+
+/// When frames are shown, see [`PresentMode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Presentation {
+    /// Wait for the display to refresh, dropping no frames.
+    Fifo,
+    /// Like `fifo`, but show late frames right away, which may tear.
+    FifoRelaxed,
+    /// Wait for the display to refresh, replacing frames not shown yet with newer ones.
+    Mailbox,
+    /// Show frames right away, which may tear.
+    Immediate,
+}
+
+impl From<Presentation> for PresentMode {
+    fn from(presentation: Presentation) -> Self {
+        match presentation {
+            Presentation::Fifo => Self::Fifo,
+            Presentation::FifoRelaxed => Self::FifoRelaxed,
+            Presentation::Mailbox => Self::Mailbox,
+            Presentation::Immediate => Self::Immediate,
+        }
+    }
+}
+
+/// How the window is composited with whatever is behind it, see [`CompositeAlphaMode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AlphaMode {
+    /// `opaque` or `inherit`, whichever is supported.
+    Auto,
+    /// Ignore the alpha channel.
+    Opaque,
+    /// Colours are multiplied by their alpha already.
+    PreMultiplied,
+    /// Colours get multiplied by their alpha when composited.
+    PostMultiplied,
+    /// Leave it to the window system.
+    Inherit,
+}
+
+impl From<AlphaMode> for CompositeAlphaMode {
+    fn from(alpha_mode: AlphaMode) -> Self {
+        match alpha_mode {
+            AlphaMode::Auto => Self::Auto,
+            AlphaMode::Opaque => Self::Opaque,
+            AlphaMode::PreMultiplied => Self::PreMultiplied,
+            AlphaMode::PostMultiplied => Self::PostMultiplied,
+            AlphaMode::Inherit => Self::Inherit,
+        }
+    }
+}
+
+/// Command line flags choosing how the window is presented to, see [`SurfaceOptions`].
+#[derive(Debug, Clone, Default, Args)]
+pub struct SurfaceCli {
+    /// Fifo by default, which paces redraws to the refresh rate of the display.
+    #[arg(long, value_enum)]
+    pub present_mode: Option<Presentation>,
+
+    #[arg(long, value_enum)]
+    pub alpha_mode: Option<AlphaMode>,
+
+    /// Prefer a surface format without sRGB encoding.
+    #[arg(long)]
+    pub no_srgb: bool,
+}
+
+impl SurfaceCli {
+    pub fn apply(&self, mut options: SurfaceOptions) -> SurfaceOptions {
+        if let Some(present_mode) = self.present_mode {
+            options = options.with_present_mode(present_mode.into());
+        }
+        if let Some(alpha_mode) = self.alpha_mode {
+            options = options.with_alpha_mode(alpha_mode.into());
+        }
+        options.with_srgb(options.srgb && !self.no_srgb)
+    }
+}
@@ -1,2 +1,5 @@
+pub mod adapters;
 pub mod basic;
+pub mod compare;
 pub mod fit;
+pub mod headless;
@@ -0,0 +1,53 @@
+use clap::Parser;
+use color_eyre::eyre::Result;
+
+use crate::app::{AdapterCli, ContextOptions, NoAdapter};
+
+/// Lists the adapters that the other commands can run on, with their features and limits.
+#[derive(Debug, Clone, Parser)]
+pub struct Cli {
+    #[command(flatten)]
+    pub adapter: AdapterCli,
+}
+
+impl Cli {
+    pub fn run(self) -> Result<()> {
+        let options = self.adapter.apply(ContextOptions::default());
+        let adapters = options.enumerate_adapters();
+        if adapters.is_empty() {
+            return Err(NoAdapter.into());
+        }
+
+        // windows also need the adapter to present to them, so they may pick another one
+        let selected = futures::executor::block_on(options.adapter())
+            .ok()
+            .map(|adapter| adapter.get_info());
+
+        for (idx, adapter) in adapters.iter().enumerate() {
+            let info = adapter.get_info();
+            let selected = if selected.as_ref() == Some(&info) {
+                " (selected)"
+            } else {
+                ""
+            };
+            println!(
+                "#{idx}: {} ({:?}, {:?}){selected}",
+                info.name, info.backend, info.device_type
+            );
+            if !info.driver.is_empty() {
+                println!("  driver: {} {}", info.driver, info.driver_info);
+            }
+            println!("  features: {:?}", adapter.features());
+            println!("  limits:");
+            // without the braces around the fields
+            let limits = format!("{:#?}", adapter.limits());
+            for line in limits
+                .lines()
+                .filter(|line| !line.ends_with('{') && *line != "}")
+            {
+                println!("    {}", line.trim());
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,48 +1,186 @@
 use std::{
-    fs::{File, OpenOptions},
-    iter,
+    f32::consts::PI,
+    fs::{self, File, OpenOptions},
+    iter, mem,
+    num::NonZero,
+    ops::RangeInclusive,
     path::PathBuf,
     sync::{
+        atomic::{AtomicUsize, Ordering},
         mpsc::{self, TryRecvError},
-        Arc,
+        Arc, Mutex,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use clap::Parser;
-use color_eyre::eyre::{Ok, Result};
+use color_eyre::eyre::{Ok, Result, WrapErr};
+use egui::{
+    Align2, Button, CollapsingHeader, Grid, ScrollArea, SidePanel, Slider, SliderClamping, Window,
+};
 use futures::future::BoxFuture;
-use glam::{vec2, Affine2, Vec2};
+use glam::{vec2, Affine2, DAffine2, Vec2};
+use itertools::Itertools;
+use log::{error, info, warn};
 use rand::Rng;
+use tokio::time::MissedTickBehavior;
 use wgpu::{
-    BufferUsages, CommandEncoderDescriptor, Extent3d, Origin3d, SurfaceConfiguration,
+    BufferUsages, CommandEncoderDescriptor, Extent3d, Features, Origin3d, SurfaceConfiguration,
     TexelCopyBufferInfo, TexelCopyBufferLayout, TexelCopyTextureInfo, Texture, TextureDescriptor,
-    TextureUsages, TextureView, TextureViewDescriptor,
+    TextureUsages,
+};
+use winit::{
+    dpi::{LogicalSize, PhysicalSize},
+    event::WindowEvent,
+    window::WindowAttributes,
 };
-use winit::{dpi::LogicalSize, event::WindowEvent, window::WindowAttributes};
 
 use crate::{
-    app::{self, Context, LocalAppController, Run},
+    app::{
+        self, AdapterCli, Context, ContextOptions, FramePacing, LocalAppController, Run,
+        StepBudget, SurfaceCli, SurfaceOptions,
+    },
     buffer::Buffer,
+    camera::CameraController,
+    controls::{Action, KeyBinding, KeyBindings},
     map::*,
-    render::{Camera, Renderer},
-    sim::{Point, Simulation},
-    util::Affine2Ext,
+    palette::{BuiltinPalette, Palette, PaletteSource},
+    profiler::Profiler,
+    render::{
+        BlendMode, Camera, HexColor, MapOverlay, PointStyle, RenderSettings, Renderer,
+        DENSITY_FEATURES,
+    },
+    sim::{Escape, Point, Respawn, Simulation},
+    state::SimulationState,
+    util::{Affine2Ext, SyncingFuture},
+    zoom::DeepZoom,
 };
 
 #[derive(Debug, Clone, Parser)]
 pub struct Cli {
-    #[arg(short)]
-    pub n_points: usize,
+    #[arg(short, required_unless_present = "load")]
+    pub n_points: Option<usize>,
+
+    /// Keep adding points up to this many while the view and maps are left alone, going back to
+    /// `-n` points whenever they change.
+    #[arg(long)]
+    pub refine_to: Option<usize>,
 
     #[arg(short, long = "delta", default_value_t = 250)]
     pub delta_time_ms: u64,
 
+    /// Redraw at most this many times per second instead of at every display refresh.
+    #[arg(long, value_parser = FramePacing::parse_max_fps)]
+    pub max_fps: Option<f32>,
+
+    /// Take at most this many steps per redraw, however short `--delta` is.
+    #[arg(long)]
+    pub steps_per_frame: Option<NonZero<u32>>,
+
     #[arg(short, long, requires = "n_gens")]
     pub out: Option<PathBuf>,
 
     #[arg(short = 'g', long, requires = "out")]
     pub n_gens: Option<usize>,
+
+    #[arg(long)]
+    pub escape_radius: Option<f32>,
+
+    #[arg(long, value_enum, default_value_t)]
+    pub respawn: Respawn,
+
+    /// Resume from a simulation state saved with `--save`.
+    #[arg(long)]
+    pub load: Option<PathBuf>,
+
+    /// Save the simulation state when the window is closed.
+    #[arg(long)]
+    pub save: Option<PathBuf>,
+
+    /// Log statistics about the points every this many steps.
+    #[arg(long)]
+    pub stats_every: Option<NonZero<usize>>,
+
+    /// Colour of the points, as `#rrggbb` or `#rrggbbaa`.
+    #[arg(long, default_value = "#ffffff")]
+    pub foreground: HexColor,
+
+    /// Colour behind the points, which may be transparent.
+    #[arg(long, default_value = "#000000")]
+    pub background: HexColor,
+
+    #[arg(long, value_enum, default_value_t)]
+    pub blend: BlendMode,
+
+    #[arg(long, value_enum, default_value_t)]
+    pub point_style: PointStyle,
+
+    /// Diameter of the points in pixels, unless drawn as single pixels.
+    #[arg(long, default_value_t = 3.0)]
+    pub point_size: f32,
+
+    /// Samples per pixel for multisample anti-aliasing, e.g. 4.
+    #[arg(long, default_value_t = 1)]
+    pub msaa: u32,
+
+    /// Fade the previous frames by this factor instead of clearing them, e.g. 0.9, leaving
+    /// trails behind the points as they move.
+    #[arg(long)]
+    pub trails: Option<f32>,
+
+    /// Colour the density of the points with a palette: `viridis`, `magma`, `inferno`, or the path
+    /// of a `.map` or CSV file. Press P to cycle through the built-in palettes.
+    #[arg(long)]
+    pub palette: Option<PaletteSource>,
+
+    /// Number of points in a pixel at which the palette reaches its last colour.
+    #[arg(long, default_value_t = 256.0)]
+    pub saturation: f32,
+
+    /// Save a preview strip of the palette as an image.
+    #[arg(long, requires = "palette")]
+    pub palette_swatch: Option<PathBuf>,
+
+    /// How the region is fitted into the window when their aspect ratios differ.
+    #[arg(long, value_enum, default_value_t)]
+    pub aspect: AspectMode,
+
+    /// Outline the region and its image under each map. Press O to toggle.
+    #[arg(long)]
+    pub overlay: bool,
+
+    /// Draw the outlines of `--overlay` into the recording too.
+    #[arg(long, requires = "out", conflicts_with = "deep_zoom")]
+    pub bake_overlay: bool,
+
+    /// Re-centre the simulation on the view whenever it changes, so that single precision holds
+    /// up when zooming in far.
+    #[arg(long)]
+    pub deep_zoom: bool,
+
+    /// Show a side panel to edit the maps while the simulation runs.
+    #[arg(long)]
+    pub gui: bool,
+
+    /// Bind a key to an action instead of its default keys, e.g. `pause=p` or `zoom-in=pageup`.
+    /// Press H to list the key bindings. The camera zooms with PageUp and PageDown and resets with
+    /// Home, e.g. `zoom-in=+` restores its earlier key.
+    #[arg(long = "bind", value_name = "ACTION=KEY")]
+    pub bindings: Vec<KeyBinding>,
+
+    /// Time the simulation and render passes, and log the results when the window is closed.
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Export pass times as JSON when the window is closed.
+    #[arg(long, requires = "profile")]
+    pub profile_json: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub adapter: AdapterCli,
+
+    #[command(flatten)]
+    pub surface: SurfaceCli,
 }
 
 impl Cli {
@@ -101,32 +239,129 @@ impl Cli {
         // ];
 
         let maps = Pentagon.maps();
+        let region = Pentagon.region();
+
+        let state = self.load.map(SimulationState::load).transpose()?;
+
+        let palette = self.palette.as_ref().map(PaletteSource::load).transpose()?;
+        if let (Some(palette), Some(path)) = (&palette, &self.palette_swatch) {
+            palette.swatch(512, 64).save(path)?;
+        }
 
         Run::new(AppBuilder {
-            region: Rect {
-                min: Vec2::NEG_ONE,
-                max: Vec2::ONE,
-            },
+            region,
             maps,
-            n_points: self.n_points,
+            n_points: self.n_points.unwrap_or_default(),
+            refine_to: self.refine_to,
             delta_time: Duration::from_millis(self.delta_time_ms),
+            steps_per_frame: self.steps_per_frame,
+            escape_radius: self.escape_radius,
+            respawn: self.respawn,
+            state,
+            save: self.save,
+            stats_every: self.stats_every,
+            render_settings: RenderSettings::default()
+                .with_foreground(self.foreground.0)
+                .with_background(self.background.0)
+                .with_blend(self.blend)
+                .with_saturation(self.saturation)
+                .with_point_style(self.point_style)
+                .with_point_size(self.point_size)
+                .with_samples(self.msaa)
+                .with_trails(self.trails),
+            palette,
+            builtin_palette: match self.palette {
+                Some(PaletteSource::Builtin(builtin)) => Some(builtin),
+                _ => None,
+            },
+            aspect: self.aspect,
+            overlay: self.overlay,
+            bake_overlay: self.bake_overlay,
+            deep_zoom: self.deep_zoom,
+            profile: self.profile,
+            profile_json: self.profile_json,
             record,
+            bindings: KeyBindings::default().with_overrides(&self.bindings),
+            show_panel: self.gui,
+            camera: None,
+            paused: false,
         })
+        .with_context_options(self.adapter.apply(ContextOptions::default()))
+        .with_surface_options(self.surface.apply(SurfaceOptions::default()))
         .with_window_attributes(
             WindowAttributes::default().with_inner_size(LogicalSize::new(800, 800)),
         )
+        .with_optional_features(
+            DENSITY_FEATURES
+                | if self.profile {
+                    Features::TIMESTAMP_QUERY
+                } else {
+                    Features::empty()
+                },
+        )
+        // also draws the key bindings
+        .with_gui(true)
+        .with_frame_pacing(self.max_fps.map_or(FramePacing::Vsync, FramePacing::MaxFps))
         .run()
     }
 }
 
 const TEXTURE_DIM: usize = 64;
 
+/// Factor by which [`Action::Faster`] and [`Action::Slower`] change the interval between steps.
+const DELTA_TIME_STEP: f64 = 1.25;
+const MIN_DELTA_TIME: Duration = Duration::from_millis(1);
+// how often a paused simulation checks for controls
+const PAUSED_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long the view and maps must be left alone before `--refine-to` adds points.
+const REFINE_DELAY: Duration = Duration::from_millis(500);
+// steps between checks for escaped points, each of which waits for the GPU
+const RESPAWN_CHECK_INTERVAL: usize = 32;
+
 struct AppBuilder {
     region: Rect,
     maps: Vec<Map>,
     n_points: usize,
+    refine_to: Option<usize>,
     delta_time: Duration,
+    steps_per_frame: Option<NonZero<u32>>,
+    escape_radius: Option<f32>,
+    respawn: Respawn,
+    state: Option<SimulationState>,
+    save: Option<PathBuf>,
+    stats_every: Option<NonZero<usize>>,
+    render_settings: RenderSettings,
+    palette: Option<Palette>,
+    builtin_palette: Option<BuiltinPalette>,
+    aspect: AspectMode,
+    overlay: bool,
+    bake_overlay: bool,
+    deep_zoom: bool,
+    profile: bool,
+    profile_json: Option<PathBuf>,
     record: Option<RecordConfig>,
+    bindings: KeyBindings,
+    show_panel: bool,
+    // the camera unless there is a saved `state`, and whether to start paused, e.g. when
+    // rebuilding on a new device
+    camera: Option<DAffine2>,
+    paused: bool,
+}
+
+impl AppBuilder {
+    // everything but what building uses up, to build again after the device was lost
+    fn config(&self) -> Self {
+        Self {
+            maps: self.maps.clone(),
+            state: None,
+            save: self.save.clone(),
+            palette: self.palette.clone(),
+            profile_json: self.profile_json.clone(),
+            record: None,
+            bindings: self.bindings.clone(),
+            ..*self
+        }
+    }
 }
 
 struct RecordConfig {
@@ -137,10 +372,57 @@ struct RecordConfig {
 }
 
 struct App {
-    simulation: Arc<Simulation<Buffer<Point>>>,
+    simulation: Arc<Mutex<Simulation<Buffer<Point>>>>,
+    // in world coordinates, as edited in the GUI
+    maps: Vec<Map>,
+    region: Rect,
+    last_frame: Option<Instant>,
+    fps: f32,
     renderer: Renderer,
     camera: Arc<Camera>,
-    stop_simulation_tx: mpsc::Sender<()>,
+    camera_controller: CameraController,
+    record_camera: Option<(Arc<Camera>, PhysicalSize<u32>)>,
+    deep_zoom: Option<DeepZoom>,
+    overlay: MapOverlay,
+    show_overlay: bool,
+    builtin_palette: Option<BuiltinPalette>,
+    save: Option<PathBuf>,
+    profiler: Option<Arc<Profiler>>,
+    profile_json: Option<PathBuf>,
+    // whether the window was closed and the app exits once the state is saved
+    exiting: bool,
+    bindings: KeyBindings,
+    show_panel: bool,
+    show_help: bool,
+    paused: bool,
+    delta_time: Duration,
+    control_tx: mpsc::Sender<Control>,
+    // controls sent to the simulation task, and those it handled by the last frame, which
+    // decides whether a paused simulation still needs redrawing
+    controls_sent: usize,
+    controls_shown: usize,
+    controls_handled: Arc<AtomicUsize>,
+    step_budget: Arc<StepBudget>,
+    // points to go back to whenever the view or maps change, and to add up to otherwise
+    base_points: usize,
+    refine_to: Option<usize>,
+    // as last requested from the simulation task
+    point_count: usize,
+    last_change: Instant,
+    // what the app was built from, to rebuild it on a new device
+    config: AppBuilder,
+}
+
+/// Sent from the window to the simulation task.
+#[derive(Debug, Clone, Copy)]
+enum Control {
+    Stop,
+    TogglePause,
+    Step,
+    Reset,
+    Reseed(u32),
+    SetDeltaTime(Duration),
+    Resize(usize),
 }
 
 struct Record {
@@ -149,9 +431,9 @@ struct Record {
     width: u16,
     height: u16,
     texture: Texture,
-    texture_view: TextureView,
     buffer: Buffer<u8>,
     renderer: Renderer,
+    camera: Arc<Camera>,
 }
 
 impl app::AppBuilder for AppBuilder {
@@ -162,40 +444,100 @@ impl app::AppBuilder for AppBuilder {
         surface_configuration: &SurfaceConfiguration,
         context: Context,
     ) -> BoxFuture<'static, Result<Self::App>> {
-        env_logger::init();
+        let config = self.config();
+        let (mut simulation, saved_camera) = match &self.state {
+            Some(state) => (
+                Simulation::load(state, context.borrow()),
+                Some(state.frame * state.camera),
+            ),
+            None => {
+                let transform = self.region.to_clip_transform();
 
-        let transform = self.region.to_clip_transform();
+                let mut rng = rand::rng();
+                let points: Vec<_> = iter::repeat_with(|| Point {
+                    position: transform.transform_point2(Vec2::new(
+                        rng.random_range(-1.0..=1.0),
+                        rng.random_range(-1.0..=1.0),
+                    )),
+                })
+                .take(self.n_points)
+                .collect();
+                // let points: Vec<_> = iter::repeat_with(|| Point {
+                //     position: transform.transform_point2(Vec2::new(
+                //         rng.random_range(-1.0e-5..=1.0e-5),
+                //         rng.random_range(-1.0e-5..=1.0e-5),
+                //     )),
+                // })
+                // .take(self.n_points)
+                // .collect();
 
-        let mut rng = rand::rng();
-        let points: Vec<_> = iter::repeat_with(|| Point {
-            position: transform.transform_point2(Vec2::new(
-                rng.random_range(-1.0..=1.0),
-                rng.random_range(-1.0..=1.0),
-            )),
-        })
-        .take(self.n_points)
-        .collect();
-        // let points: Vec<_> = iter::repeat_with(|| Point {
-        //     position: transform.transform_point2(Vec2::new(
-        //         rng.random_range(-1.0e-5..=1.0e-5),
-        //         rng.random_range(-1.0e-5..=1.0e-5),
-        //     )),
-        // })
-        // .take(self.n_points)
-        // .collect();
-
-        let point_buffer = Buffer::from_data(
-            &points,
-            Some("Points"),
-            BufferUsages::STORAGE | BufferUsages::VERTEX,
-            context.borrow(),
-        );
+                let point_buffer = Buffer::from_data(
+                    &points,
+                    Some("Points"),
+                    BufferUsages::STORAGE | BufferUsages::VERTEX | BufferUsages::COPY_SRC,
+                    context.borrow(),
+                );
+
+                let mut simulation = Simulation::new(point_buffer, &self.maps, context.borrow());
+                let mut escape = Escape::new(self.region).with_respawn(self.respawn);
+                if let Some(radius) = self.escape_radius {
+                    escape = escape.with_radius(radius);
+                }
+                simulation.set_escape(escape, context.borrow());
+                (simulation, self.camera)
+            }
+        };
+        simulation.set_statistics_interval(self.stats_every, context.borrow());
+        let mut renderer = Renderer::new(context.borrow(), surface_configuration.format);
+        renderer.set_settings(self.render_settings, context.borrow());
+        renderer.set_palette(self.palette.as_ref(), context.borrow());
+
+        // in world coordinates, unlike those of a state saved while deep zooming
+        let deep_zoom = match &self.state {
+            Some(state) => DeepZoom::from_state(state),
+            None => {
+                let mut deep_zoom = DeepZoom::new(&[], *simulation.escape());
+                deep_zoom.set_maps(simulation.maps());
+                deep_zoom
+            }
+        };
+        let maps: Vec<_> = deep_zoom.maps().iter().copied().map(Map::from).collect();
+        let mut overlay = MapOverlay::new(self.region, deep_zoom.maps().iter().copied());
+        overlay.frame = deep_zoom.frame();
+        if self.overlay {
+            renderer.set_overlay(Some(&overlay), context.borrow());
+        }
 
-        let simulation = Arc::new(Simulation::new(point_buffer, &self.maps, context.borrow()));
-        let renderer = Renderer::new(context.borrow(), surface_configuration.format);
-        let camera = Arc::new(Camera::new(transform, context.borrow()));
+        let profiler = self
+            .profile
+            .then(|| Arc::new(Profiler::new(context.borrow())));
+        simulation.set_profiler(profiler.clone());
+        renderer.set_profiler(profiler.clone());
+
+        // the points of a state saved while deep zooming are in local coordinates
+        let deep_zoom =
+            (self.deep_zoom || deep_zoom.frame() != DAffine2::IDENTITY).then_some(deep_zoom);
+        let local_camera = |camera: DAffine2| match &deep_zoom {
+            Some(deep_zoom) => deep_zoom.local_camera(camera),
+            None => camera.as_affine2(),
+        };
+
+        let mut camera_controller = CameraController::new(
+            self.region,
+            self.aspect,
+            PhysicalSize::new(surface_configuration.width, surface_configuration.height),
+        )
+        .with_key_bindings(self.bindings.clone());
+        if let Some(camera) = saved_camera {
+            camera_controller.set_transform(camera);
+        }
 
-        let mut record = self.record.map(
+        let simulation = Arc::new(Mutex::new(simulation));
+        let camera = Arc::new(Camera::new(
+            local_camera(camera_controller.transform()),
+            context.borrow(),
+        ));
+        let record = self.record.map(
             |RecordConfig {
                  encoder,
                  n_gens,
@@ -217,11 +559,6 @@ impl app::AppBuilder for AppBuilder {
                     view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
                 });
 
-                let texture_view = texture.create_view(&TextureViewDescriptor {
-                    label: Some("Simulation Texture View"),
-                    ..Default::default()
-                });
-
                 let buffer = Buffer::from_data(
                     &vec![0; TEXTURE_DIM * TEXTURE_DIM * 4],
                     Some("Simulation Texture Buffer"),
@@ -229,7 +566,21 @@ impl app::AppBuilder for AppBuilder {
                     context.borrow(),
                 );
 
-                let renderer = Renderer::new(context.borrow(), wgpu::TextureFormat::Rgba8Unorm);
+                let mut renderer = Renderer::new(context.borrow(), wgpu::TextureFormat::Rgba8Unorm);
+                renderer.set_settings(self.render_settings, context.borrow());
+                renderer.set_palette(self.palette.as_ref(), context.borrow());
+                if self.bake_overlay {
+                    renderer.set_overlay(Some(&overlay), context.borrow());
+                }
+
+                // follows the window's view, fitted to the recording's own size
+                let camera = Camera::new(
+                    local_camera(
+                        camera_controller
+                            .transform_for(PhysicalSize::new(width.into(), height.into())),
+                    ),
+                    context.borrow(),
+                );
 
                 Record {
                     encoder,
@@ -237,80 +588,270 @@ impl app::AppBuilder for AppBuilder {
                     width,
                     height,
                     texture,
-                    texture_view,
                     buffer,
                     renderer,
+                    camera: Arc::new(camera),
                 }
             },
         );
+        let record_camera = record.as_ref().map(|record| {
+            let size = PhysicalSize::new(record.width.into(), record.height.into());
+            (record.camera.clone(), size)
+        });
 
-        let (stop_simulation_tx, stop_simulation_rx) = mpsc::channel();
+        let (control_tx, control_rx) = mpsc::channel();
+        let controls_handled = Arc::new(AtomicUsize::new(0));
+        let controls_handled2 = controls_handled.clone();
+        let step_budget = Arc::new(StepBudget::new(self.steps_per_frame));
+        let step_budget2 = step_budget.clone();
         let simulation2 = simulation.clone();
         let context2 = context.to_static();
-        let camera2 = camera.clone();
+        let profiler2 = profiler.clone();
 
         context.borrow().runtime().spawn(async move {
-            let context = context2;
-            let simulation = simulation2;
-
-            let mut gen_iter = if let Some(record) = &mut record {
-                record
-                    .encoder
-                    .set_repeat(gif::Repeat::Infinite)
-                    .expect("failed to set repeating behavior of GIF");
-
-                drop(record.renderer.render(
-                    simulation.points(),
-                    &camera2,
-                    &record.texture_view,
-                    context.borrow(),
-                ));
+            let result = simulate(
+                simulation2,
+                record,
+                self.delta_time,
+                self.paused,
+                control_rx,
+                controls_handled2,
+                step_budget2,
+                profiler2,
+                context2,
+            )
+            .await;
+            if let Err(error) = result {
+                error!("the simulation stopped: {error:?}");
+            }
+        });
 
-                record
-                    .write_frame(self.delta_time, context.borrow())
-                    .await
-                    .expect("failed to write frame");
+        let base_points = simulation
+            .lock()
+            .expect("failed to lock mutex")
+            .points()
+            .len();
+        let app = App {
+            maps,
+            region: self.region,
+            last_frame: None,
+            fps: 0.0,
+            simulation,
+            renderer,
+            camera,
+            camera_controller,
+            record_camera,
+            deep_zoom,
+            overlay,
+            show_overlay: self.overlay,
+            builtin_palette: self.builtin_palette,
+            save: self.save,
+            profiler,
+            profile_json: self.profile_json,
+            exiting: false,
+            bindings: self.bindings,
+            show_panel: self.show_panel,
+            show_help: false,
+            paused: self.paused,
+            delta_time: self.delta_time,
+            control_tx,
+            controls_sent: 0,
+            controls_shown: 0,
+            controls_handled,
+            step_budget,
+            base_points,
+            refine_to: self.refine_to,
+            point_count: base_points,
+            last_change: Instant::now(),
+            config,
+        };
 
-                Some(0..record.n_gens)
-            } else {
-                None
-            };
+        Box::pin(async move { Ok(app) })
+    }
 
-            let mut interval = tokio::time::interval(self.delta_time);
+    fn rebuild(
+        app: Self::App,
+        surface_configuration: &SurfaceConfiguration,
+        context: Context,
+    ) -> BoxFuture<'static, Result<Self::App>> {
+        warn!("the points were lost with the device, restarting the simulation");
+        if app.record_camera.is_some() {
+            warn!("stopped recording");
+        }
+        let builder = AppBuilder {
+            maps: app.maps.clone(),
+            n_points: app.base_points,
+            delta_time: app.delta_time,
+            palette: app
+                .builtin_palette
+                .map(BuiltinPalette::palette)
+                .or_else(|| app.config.palette.clone()),
+            builtin_palette: app.builtin_palette,
+            overlay: app.show_overlay,
+            show_panel: app.show_panel,
+            camera: Some(app.camera_controller.transform()),
+            paused: app.paused,
+            ..app.config.config()
+        };
+        // stops the old simulation
+        drop(app);
+        builder.build(surface_configuration, context)
+    }
+}
 
-            while stop_simulation_rx.try_recv() == Err(TryRecvError::Empty)
-                && gen_iter.as_mut().is_none_or(|gen| gen.next().is_some())
-            {
-                interval.tick().await;
-                simulation.step(context.borrow()).await;
+/// Steps the simulation every `delta_time` as the [`StepBudget`] allows, handling controls from
+/// the app and writing each step to `record` if any, until it's stopped.
+#[allow(clippy::too_many_arguments)]
+async fn simulate(
+    simulation: Arc<Mutex<Simulation<Buffer<Point>>>>,
+    mut record: Option<Record>,
+    mut delta_time: Duration,
+    mut paused: bool,
+    control_rx: mpsc::Receiver<Control>,
+    controls_handled: Arc<AtomicUsize>,
+    step_budget: Arc<StepBudget>,
+    profiler: Option<Arc<Profiler>>,
+    context: Context<'static>,
+) -> Result<()> {
+    let mut gen_iter = if let Some(record) = &mut record {
+        record
+            .encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .wrap_err("failed to set repeating behavior of GIF")?;
 
-                let Some(record) = &mut record else {
-                    continue;
-                };
+        drop(record.renderer.render(
+            simulation.lock().expect("failed to lock mutex").points(),
+            &record.camera,
+            &record.texture,
+            context.borrow(),
+        ));
 
-                drop(record.renderer.render(
-                    simulation.points(),
-                    &camera2,
-                    &record.texture_view,
-                    context.borrow(),
-                ));
+        record
+            .write_frame(delta_time, context.borrow())
+            .await
+            .wrap_err("failed to write frame")?;
+
+        Some(0..record.n_gens)
+    } else {
+        None
+    };
+
+    let mut interval = tokio::time::interval(delta_time);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
-                record
-                    .write_frame(self.delta_time, context.borrow())
-                    .await
-                    .expect("failed to write frame");
+    'simulation: loop {
+        if paused {
+            tokio::time::sleep(PAUSED_POLL_INTERVAL).await;
+        } else {
+            interval.tick().await;
+        }
+
+        let mut single_steps = 0;
+        loop {
+            let control = control_rx.try_recv();
+            match control {
+                Result::Ok(Control::TogglePause) => paused = !paused,
+                Result::Ok(Control::Step) => single_steps += 1,
+                Result::Ok(Control::Reset) => {
+                    let reset = simulation
+                        .lock()
+                        .expect("failed to lock mutex")
+                        .reset_points(context.borrow());
+                    reset.await;
+                }
+                Result::Ok(Control::Reseed(seed)) => simulation
+                    .lock()
+                    .expect("failed to lock mutex")
+                    .set_seed(seed, context.borrow()),
+                Result::Ok(Control::Resize(len)) => {
+                    let resize = simulation
+                        .lock()
+                        .expect("failed to lock mutex")
+                        .resize(len, context.borrow());
+                    resize.await;
+                }
+                Result::Ok(Control::SetDeltaTime(new_delta_time)) => {
+                    delta_time = new_delta_time;
+                    interval = tokio::time::interval(delta_time);
+                    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                }
+                Result::Ok(Control::Stop) | Err(TryRecvError::Disconnected) => break 'simulation,
+                Err(TryRecvError::Empty) => break,
             }
-        });
+            if !matches!(control, Result::Ok(Control::Step)) {
+                controls_handled.fetch_add(1, Ordering::Relaxed);
+            }
+        }
 
-        let app = App {
-            simulation,
-            renderer,
-            camera,
-            stop_simulation_tx,
+        // single steps ignore the budget, each press taking a step of its own
+        let steps = if single_steps > 0 {
+            single_steps
+        } else if paused || !step_budget.take() {
+            continue;
+        } else {
+            1
         };
+        for _ in 0..steps {
+            if gen_iter.as_mut().is_some_and(|gen| gen.next().is_none()) {
+                break 'simulation;
+            }
 
-        Box::pin(async move { Ok(app) })
+            // only lock while encoding, so that the view can re-centre the simulation
+            let step = simulation
+                .lock()
+                .expect("failed to lock mutex")
+                .step(context.borrow());
+            step.await;
+            if single_steps > 0 {
+                controls_handled.fetch_add(1, Ordering::Relaxed);
+            }
+
+            let respawn_count = {
+                let simulation = simulation.lock().expect("failed to lock mutex");
+                simulation
+                    .step_count()
+                    .is_multiple_of(RESPAWN_CHECK_INTERVAL)
+                    .then(|| simulation.take_respawn_count(context.borrow()))
+            };
+            if let Some(respawn_count) = respawn_count {
+                let respawn_count = respawn_count.await?;
+                if respawn_count > 0 {
+                    warn!(
+                        "{respawn_count} points escaped and were respawned, the maps may diverge"
+                    );
+                }
+            }
+
+            let statistics = simulation
+                .lock()
+                .expect("failed to lock mutex")
+                .take_statistics(context.borrow());
+            if let Some(statistics) = statistics.await? {
+                info!("{statistics}");
+            }
+
+            if let Some(profiler) = &profiler {
+                profiler.resolve(context.borrow()).await;
+            }
+
+            let Some(record) = &mut record else {
+                continue;
+            };
+
+            drop(record.renderer.render(
+                simulation.lock().expect("failed to lock mutex").points(),
+                &record.camera,
+                &record.texture,
+                context.borrow(),
+            ));
+
+            record
+                .write_frame(delta_time, context.borrow())
+                .await
+                .wrap_err("failed to write frame")?;
+        }
     }
+    Ok(())
 }
 
 impl Record {
@@ -347,7 +888,7 @@ impl Record {
         slice
             .map_async(wgpu::MapMode::Read)
             .await
-            .expect("failed to map buffer");
+            .wrap_err("failed to map buffer for reading")?;
         let mut bytes = slice.get_mapped_range().to_vec();
         self.buffer.unmap();
 
@@ -358,11 +899,241 @@ impl Record {
     }
 }
 
+impl App {
+    // `camera` in the coordinates of the simulation
+    fn local_camera(&self, camera: DAffine2) -> DAffine2 {
+        match &self.deep_zoom {
+            Some(deep_zoom) => deep_zoom.local_camera(camera).as_daffine2(),
+            None => camera,
+        }
+    }
+
+    fn update_camera(&mut self, context: Context) {
+        // re-centring loses a little precision each time, so wait for drags to end
+        if let Some(deep_zoom) = self
+            .deep_zoom
+            .as_mut()
+            .filter(|_| !self.camera_controller.is_dragging())
+        {
+            let transform = deep_zoom.recentre(self.camera_controller.transform());
+            let mut simulation = self.simulation.lock().expect("failed to lock mutex");
+            simulation
+                .transform_points(transform, context.borrow())
+                .ignore();
+            simulation.set_dmaps(&deep_zoom.local_maps(), context.borrow());
+            simulation.set_escape(deep_zoom.local_escape(), context.borrow());
+            drop(simulation);
+
+            self.overlay.frame = deep_zoom.frame();
+            if self.show_overlay {
+                self.renderer
+                    .set_overlay(Some(&self.overlay), context.borrow());
+            }
+        }
+
+        let camera = self.local_camera(self.camera_controller.transform());
+        self.camera
+            .set_transform(camera.as_affine2(), context.borrow());
+        // trails would smear across the window
+        self.renderer.clear_trails();
+        if let Some((record_camera, size)) = &self.record_camera {
+            let camera = self.local_camera(self.camera_controller.transform_for(*size));
+            record_camera.set_transform(camera.as_affine2(), context.borrow());
+        }
+        self.changed();
+    }
+
+    // after the view or maps changed, so that they respond quickly
+    fn changed(&mut self) {
+        self.last_change = Instant::now();
+        if self.point_count > self.base_points {
+            self.resize(self.base_points);
+        }
+    }
+
+    // once the view and maps were left alone for a while
+    fn refine(&mut self) {
+        let Some(refine_to) = self.refine_to else {
+            return;
+        };
+        // one resize at a time, as each one copies all points
+        if self.point_count < refine_to
+            && self.controls_shown == self.controls_sent
+            && self.last_change.elapsed() >= REFINE_DELAY
+        {
+            self.resize((2 * self.point_count).min(refine_to));
+        }
+    }
+
+    fn resize(&mut self, len: usize) {
+        self.point_count = len;
+        self.send(Control::Resize(len));
+    }
+}
+
+impl App {
+    // after editing `maps`
+    fn apply_maps(&mut self, context: Context) {
+        let maps = self
+            .maps
+            .iter()
+            .copied()
+            .map(DMap::from)
+            .collect::<Vec<_>>();
+        let mut simulation = self.simulation.lock().expect("failed to lock mutex");
+        match &mut self.deep_zoom {
+            Some(deep_zoom) => {
+                deep_zoom.set_maps(&maps);
+                simulation.set_dmaps(&deep_zoom.local_maps(), context.borrow());
+            }
+            None => simulation.set_dmaps(&maps, context.borrow()),
+        }
+        drop(simulation);
+
+        self.overlay.maps = maps;
+        if self.show_overlay {
+            self.renderer.set_overlay(Some(&self.overlay), context);
+        }
+        self.changed();
+    }
+
+    fn map_editor(ui: &mut egui::Ui, map: &mut Map, region: Rect, removable: bool) -> (bool, bool) {
+        let mut decomposition = MapDecomposition::from(*map);
+        let size = region.max - region.min;
+        // a map collapsing the plane onto a line can't be edited linearly without losing it
+        let linear = !decomposition.is_degenerate();
+        let mut slider =
+            |value: &mut f32, range: RangeInclusive<f32>, text: &str, enabled: bool| {
+                ui.add_enabled(
+                    enabled,
+                    Slider::new(value, range)
+                        .clamping(SliderClamping::Edits)
+                        .text(text),
+                )
+                .changed()
+            };
+
+        let mut changed = slider(&mut decomposition.angle, -PI..=PI, "Angle", linear);
+        changed |= slider(&mut decomposition.scale.x, 0.0..=1.5, "Scale X", linear);
+        changed |= slider(&mut decomposition.scale.y, -1.5..=1.5, "Scale Y", linear);
+        changed |= slider(&mut decomposition.shear, -2.0..=2.0, "Shear", linear);
+        changed |= slider(
+            &mut decomposition.translation.x,
+            region.min.x - size.x..=region.max.x + size.x,
+            "Translation X",
+            true,
+        );
+        changed |= slider(
+            &mut decomposition.translation.y,
+            region.min.y - size.y..=region.max.y + size.y,
+            "Translation Y",
+            true,
+        );
+        changed |= slider(
+            &mut decomposition.probability_weight,
+            0.01..=10.0,
+            "Weight",
+            true,
+        );
+        if changed {
+            *map = decomposition.compose();
+        }
+
+        let removed = ui.add_enabled(removable, Button::new("Remove")).clicked();
+        (changed, removed)
+    }
+}
+
+impl App {
+    fn action(&mut self, action: Action, context: Context) {
+        let control = match action {
+            Action::Pause => {
+                self.paused = !self.paused;
+                info!("{}", if self.paused { "paused" } else { "resumed" });
+                Control::TogglePause
+            }
+            Action::Step => Control::Step,
+            Action::Reset => {
+                self.renderer.clear_trails();
+                Control::Reset
+            }
+            Action::Reseed => {
+                let seed = rand::rng().random();
+                info!("reseeded with {seed}");
+                Control::Reseed(seed)
+            }
+            Action::Faster | Action::Slower => {
+                self.delta_time = if action == Action::Faster {
+                    self.delta_time.div_f64(DELTA_TIME_STEP)
+                } else {
+                    self.delta_time.mul_f64(DELTA_TIME_STEP)
+                }
+                .max(MIN_DELTA_TIME);
+                info!("stepping every {:?}", self.delta_time);
+                Control::SetDeltaTime(self.delta_time)
+            }
+            Action::Overlay => {
+                self.show_overlay = !self.show_overlay;
+                self.renderer
+                    .set_overlay(self.show_overlay.then_some(&self.overlay), context);
+                return;
+            }
+            Action::Palette => {
+                let builtin = self
+                    .builtin_palette
+                    .map_or_else(BuiltinPalette::default, BuiltinPalette::next);
+                self.renderer.set_palette(Some(&builtin.palette()), context);
+                self.builtin_palette = Some(builtin);
+                info!("switched to the {builtin:?} palette");
+                return;
+            }
+            Action::Help => {
+                self.show_help = !self.show_help;
+                return;
+            }
+            // handled by the camera controller
+            _ => return,
+        };
+        self.send(control);
+    }
+
+    fn send(&mut self, control: Control) {
+        if self.control_tx.send(control).is_err() {
+            warn!("the simulation has already stopped");
+        } else {
+            self.controls_sent += 1;
+        }
+    }
+
+    fn help(&mut self, gui: &egui::Context) {
+        let bindings = &self.bindings;
+        Window::new("Key Bindings")
+            .open(&mut self.show_help)
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .collapsible(false)
+            .resizable(false)
+            .show(gui, |ui| {
+                Grid::new("Key Bindings").striped(true).show(ui, |ui| {
+                    for (action, keys) in bindings.keys() {
+                        ui.label(keys.iter().join(", "));
+                        ui.label(action.description());
+                        ui.end_row();
+                    }
+                    ui.label("Drag");
+                    ui.label("Pan the view");
+                    ui.end_row();
+                    ui.label("Scroll");
+                    ui.label("Zoom about the cursor");
+                    ui.end_row();
+                });
+            });
+    }
+}
+
 impl Drop for App {
     fn drop(&mut self) {
-        self.stop_simulation_tx
-            .send(())
-            .expect("failed to stop simulation");
+        // the simulation may have finished recording already
+        self.control_tx.send(Control::Stop).ok();
     }
 }
 
@@ -370,21 +1141,155 @@ impl app::App for App {
     fn event(
         &mut self,
         event: winit::event::WindowEvent,
-        _context: app::Context,
+        context: app::Context,
         controller: LocalAppController,
     ) {
-        if event == WindowEvent::CloseRequested {
-            controller.exit();
+        if let Some(action) = self.bindings.action(&event) {
+            self.action(action, context.borrow());
+        }
+
+        if self.camera_controller.event(&event) {
+            self.update_camera(context.borrow());
+        }
+
+        if event == WindowEvent::CloseRequested && !mem::replace(&mut self.exiting, true) {
+            // downloaded on the runtime so the window keeps responding, exiting once written
+            let save = self.save.clone().map(|path| {
+                let state = self.simulation.lock().expect("failed to lock mutex").state(
+                    self.local_camera(self.camera_controller.transform()),
+                    context.borrow(),
+                );
+                let deep_zoom = self.deep_zoom.as_ref().map(|deep_zoom| {
+                    (
+                        deep_zoom.maps().to_vec(),
+                        *deep_zoom.escape(),
+                        deep_zoom.frame(),
+                    )
+                });
+                (path, state, deep_zoom)
+            });
+            let profile = self.profiler.clone().map(|profiler| {
+                let resolved = profiler.resolve(context.borrow());
+                (profiler, resolved, self.profile_json.clone())
+            });
+            let controller = controller.into_non_local();
+            context.runtime().spawn(async move {
+                if let Some((path, state, deep_zoom)) = save {
+                    let state = state.await.map(|state| match deep_zoom {
+                        Some((maps, escape, frame)) => SimulationState {
+                            maps,
+                            escape,
+                            frame,
+                            ..state
+                        },
+                        None => state,
+                    });
+                    if let Err(error) = state.and_then(|state| state.save(&path)) {
+                        error!("failed to save simulation state: {error:?}");
+                    } else {
+                        info!("saved simulation state to {}", path.display());
+                    }
+                }
+                if let Some((profiler, resolved, json_path)) = profile {
+                    resolved.await;
+                    let report = profiler.report();
+                    info!("{report}");
+                    if let Some(path) = json_path {
+                        if let Err(error) = fs::write(path, report.to_json()) {
+                            error!("failed to export pass times: {error:?}");
+                        }
+                    }
+                }
+                controller.exit();
+            });
         }
     }
 
+    fn gui(&mut self, gui: &egui::Context, context: Context) {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame.replace(now) {
+            // smoothed over about ten frames
+            let fps = 1.0 / (now - last_frame).as_secs_f32();
+            self.fps += 0.1 * (fps - self.fps);
+        }
+
+        self.help(gui);
+        if !self.show_panel {
+            return;
+        }
+
+        SidePanel::left("Maps").show(gui, |ui| {
+            let simulation = self.simulation.lock().expect("failed to lock mutex");
+            ui.heading("Status");
+            ui.label(format!("{:.1} FPS", self.fps));
+            ui.label(format!("Step {}", simulation.step_count()));
+            if self.paused {
+                ui.label("Paused");
+            } else {
+                ui.label(format!("Stepping every {:?}", self.delta_time));
+            }
+            ui.label(format!("{} points", simulation.points().len()));
+            drop(simulation);
+            ui.separator();
+
+            ui.heading("Maps");
+            let mut changed = false;
+            let mut removed = None;
+            let removable = self.maps.len() > 1;
+            ScrollArea::vertical().show(ui, |ui| {
+                for (idx, map) in self.maps.iter_mut().enumerate() {
+                    CollapsingHeader::new(format!("Map {}", idx + 1))
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            let (map_changed, map_removed) =
+                                Self::map_editor(ui, map, self.region, removable);
+                            changed |= map_changed;
+                            if map_removed {
+                                removed = Some(idx);
+                            }
+                        });
+                }
+                if ui.button("Add Map").clicked() {
+                    self.maps
+                        .push(Map::from(Affine2::from_scale(Vec2::splat(0.5))));
+                    changed = true;
+                }
+            });
+            if let Some(idx) = removed {
+                self.maps.remove(idx);
+                changed = true;
+            }
+
+            if changed {
+                self.apply_maps(context);
+            }
+        });
+    }
+
+    fn is_animating(&self) -> bool {
+        !self.paused
+            || self.controls_shown < self.controls_sent
+            || self
+                .refine_to
+                .is_some_and(|refine_to| self.point_count < refine_to)
+    }
+
     fn render(&mut self, target: &wgpu::SurfaceTexture, context: app::Context) -> Result<()> {
-        drop(self.renderer.render(
-            self.simulation.points(),
-            &self.camera,
-            target,
-            context.borrow(),
-        ));
+        // before drawing the points, so that whatever was handled is in them
+        self.controls_shown = self.controls_handled.load(Ordering::Relaxed);
+        self.step_budget.refill();
+        self.refine();
+        drop(
+            self.renderer.render(
+                self.simulation
+                    .lock()
+                    .expect("failed to lock mutex")
+                    .points(),
+                &self.camera,
+                target,
+                context.borrow(),
+            ),
+        );
 
         Ok(())
     }
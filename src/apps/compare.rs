@@ -0,0 +1,362 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        mpsc::{self, TryRecvError},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use clap::Parser;
+use color_eyre::eyre::{Ok, Result};
+use futures::future::BoxFuture;
+use glam::DAffine2;
+use log::{info, warn};
+use wgpu::{BufferUsages, SurfaceConfiguration};
+use winit::{
+    dpi::{LogicalSize, PhysicalSize},
+    event::{ElementState, KeyEvent, WindowEvent},
+    keyboard::Key,
+    window::WindowAttributes,
+};
+
+use crate::{
+    app::{
+        self, AdapterCli, Context, ContextOptions, FramePacing, LocalAppController, Pane, Run,
+        SplitScreen, SurfaceCli, SurfaceOptions,
+    },
+    buffer::Buffer,
+    camera::CameraController,
+    map::*,
+    palette::{Palette, PaletteSource},
+    render::{BlendMode, HexColor, PointStyle, RenderSettings, Renderer, DENSITY_FEATURES},
+    sim::{Escape, Point, Simulation},
+    state::SimulationState,
+    util::SyncingFuture,
+};
+
+/// Runs several map sets side by side, each in its own viewport.
+#[derive(Debug, Clone, Parser)]
+pub struct Cli {
+    /// Built-in map sets to compare, in order.
+    #[arg(short, long, value_enum, num_args = 1.., required_unless_present = "load")]
+    pub maps: Vec<Preset>,
+
+    /// Simulation states saved with `basic --save` to compare, after `--maps`.
+    #[arg(long, num_args = 1..)]
+    pub load: Vec<PathBuf>,
+
+    /// Number of points of each of `--maps`.
+    #[arg(short, default_value_t = 100_000)]
+    pub n_points: usize,
+
+    #[arg(short, long = "delta", default_value_t = 250)]
+    pub delta_time_ms: u64,
+
+    /// Redraw at most this many times per second, see `basic --max-fps`.
+    #[arg(long, value_parser = FramePacing::parse_max_fps)]
+    pub max_fps: Option<f32>,
+
+    /// Move all views together. Press L to toggle.
+    #[arg(long)]
+    pub link: bool,
+
+    #[arg(long, default_value = "#ffffff")]
+    pub foreground: HexColor,
+
+    #[arg(long, default_value = "#000000")]
+    pub background: HexColor,
+
+    #[arg(long, value_enum, default_value_t)]
+    pub blend: BlendMode,
+
+    #[arg(long, value_enum, default_value_t)]
+    pub point_style: PointStyle,
+
+    #[arg(long, default_value_t = 3.0)]
+    pub point_size: f32,
+
+    /// Fade the previous frames by this factor instead of clearing them, see `basic --trails`.
+    #[arg(long)]
+    pub trails: Option<f32>,
+
+    /// Colour the density of the points with a palette, see `basic --palette`.
+    #[arg(long)]
+    pub palette: Option<PaletteSource>,
+
+    #[arg(long, default_value_t = 256.0)]
+    pub saturation: f32,
+
+    #[arg(long, value_enum, default_value_t)]
+    pub aspect: AspectMode,
+
+    #[command(flatten)]
+    pub adapter: AdapterCli,
+
+    #[command(flatten)]
+    pub surface: SurfaceCli,
+}
+
+impl Cli {
+    pub fn run(self) -> Result<()> {
+        let mut sources: Vec<_> = self.maps.iter().copied().map(Source::Preset).collect();
+        for path in &self.load {
+            let label = path.file_stem().map_or_else(
+                || path.display().to_string(),
+                |stem| stem.to_string_lossy().into(),
+            );
+            sources.push(Source::State(label, Arc::new(SimulationState::load(path)?)));
+        }
+        let title = sources
+            .iter()
+            .map(Source::label)
+            .collect::<Vec<_>>()
+            .join(" | ");
+        let (columns, rows) = SplitScreen::grid(sources.len());
+        let palette = self.palette.as_ref().map(PaletteSource::load).transpose()?;
+
+        Run::new(AppBuilder {
+            sources,
+            n_points: self.n_points,
+            delta_time: Duration::from_millis(self.delta_time_ms),
+            link: self.link,
+            render_settings: RenderSettings::default()
+                .with_foreground(self.foreground.0)
+                .with_background(self.background.0)
+                .with_blend(self.blend)
+                .with_saturation(self.saturation)
+                .with_point_style(self.point_style)
+                .with_point_size(self.point_size)
+                .with_trails(self.trails),
+            palette,
+            aspect: self.aspect,
+            cameras: Vec::new(),
+        })
+        .with_context_options(
+            self.adapter
+                .apply(ContextOptions::default())
+                .with_optional_features(DENSITY_FEATURES),
+        )
+        .with_surface_options(self.surface.apply(SurfaceOptions::default()))
+        .with_window_attributes(
+            WindowAttributes::default()
+                .with_title(title)
+                .with_inner_size(LogicalSize::new(500 * columns, 500 * rows)),
+        )
+        .with_frame_pacing(self.max_fps.map_or(FramePacing::Vsync, FramePacing::MaxFps))
+        // for the labels of the panes
+        .with_gui(true)
+        .run()
+    }
+}
+
+#[derive(Clone)]
+enum Source {
+    Preset(Preset),
+    State(String, Arc<SimulationState>),
+}
+
+impl Source {
+    fn label(&self) -> String {
+        match self {
+            Self::Preset(preset) => format!("{preset:?}"),
+            Self::State(label, _) => label.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct AppBuilder {
+    sources: Vec<Source>,
+    n_points: usize,
+    delta_time: Duration,
+    link: bool,
+    render_settings: RenderSettings,
+    palette: Option<Palette>,
+    aspect: AspectMode,
+    // of each pane, overriding those of the sources, e.g. to keep the views when rebuilding
+    cameras: Vec<DAffine2>,
+}
+
+struct App {
+    split: SplitScreen,
+    renderer: Renderer,
+    stop_simulation_tx: mpsc::Sender<()>,
+    // what the app was built from, to rebuild it
+    config: AppBuilder,
+}
+
+impl app::AppBuilder for AppBuilder {
+    type App = App;
+
+    fn build(
+        self,
+        surface_configuration: &SurfaceConfiguration,
+        context: Context,
+    ) -> BoxFuture<'static, Result<Self::App>> {
+        let size = PhysicalSize::new(surface_configuration.width, surface_configuration.height);
+        let viewport = SplitScreen::viewports_for(self.sources.len(), size)[0];
+        let cell = PhysicalSize::new(viewport.width, viewport.height);
+        let config = self.clone();
+
+        let panes: Vec<_> = self
+            .sources
+            .into_iter()
+            .enumerate()
+            .map(|(idx, source)| {
+                let label = source.label();
+                let (simulation, region, saved_camera) = match source {
+                    Source::Preset(preset) => {
+                        let region = preset.region();
+                        let mut rng = rand::rng();
+                        let points: Vec<_> = (0..self.n_points)
+                            .map(|_| Point::random_in(&region, &mut rng))
+                            .collect();
+                        let point_buffer = Buffer::from_data(
+                            &points,
+                            Some("Points"),
+                            BufferUsages::STORAGE | BufferUsages::VERTEX | BufferUsages::COPY_SRC,
+                            context.borrow(),
+                        );
+                        let mut simulation =
+                            Simulation::new(point_buffer, &preset.maps(), context.borrow());
+                        simulation.set_escape(Escape::new(region), context.borrow());
+                        (simulation, region, None)
+                    }
+                    Source::State(_, state) => (
+                        Simulation::load(&state, context.borrow()),
+                        state.escape.region,
+                        Some(state.camera),
+                    ),
+                };
+
+                let mut camera_controller = CameraController::new(region, self.aspect, cell);
+                if let Some(camera) = self.cameras.get(idx).copied().or(saved_camera) {
+                    camera_controller.set_transform(camera);
+                }
+                Pane::new(
+                    label,
+                    Arc::new(Mutex::new(simulation)),
+                    camera_controller,
+                    context.borrow(),
+                )
+            })
+            .collect();
+
+        let mut split = SplitScreen::new(panes, size);
+        split.set_linked(self.link, context.borrow());
+
+        let mut renderer = Renderer::new(context.borrow(), surface_configuration.format);
+        renderer.set_settings(self.render_settings, context.borrow());
+        renderer.set_palette(self.palette.as_ref(), context.borrow());
+
+        let (stop_simulation_tx, stop_simulation_rx) = mpsc::channel();
+        let simulations: Vec<_> = split
+            .panes()
+            .iter()
+            .map(|pane| pane.simulation.clone())
+            .collect();
+        let context2 = context.to_static();
+
+        context.borrow().runtime().spawn(async move {
+            let context = context2;
+            let mut interval = tokio::time::interval(self.delta_time);
+
+            while stop_simulation_rx.try_recv() == Err(TryRecvError::Empty) {
+                interval.tick().await;
+                let steps: Vec<_> = simulations
+                    .iter()
+                    .map(|simulation| {
+                        simulation
+                            .lock()
+                            .expect("failed to lock mutex")
+                            .step(context.borrow())
+                    })
+                    .collect();
+                for step in steps {
+                    step.await;
+                }
+            }
+        });
+
+        let app = App {
+            split,
+            renderer,
+            stop_simulation_tx,
+            config,
+        };
+
+        Box::pin(async move { Ok(app) })
+    }
+
+    fn rebuild(
+        app: Self::App,
+        surface_configuration: &SurfaceConfiguration,
+        context: Context,
+    ) -> BoxFuture<'static, Result<Self::App>> {
+        warn!("the points were lost with the device, restarting the simulations");
+        let builder = AppBuilder {
+            link: app.split.is_linked(),
+            cameras: app
+                .split
+                .panes()
+                .iter()
+                .map(|pane| pane.camera_controller.transform())
+                .collect(),
+            ..app.config.clone()
+        };
+        // stops the old simulations
+        drop(app);
+        builder.build(surface_configuration, context)
+    }
+}
+
+impl Drop for App {
+    fn drop(&mut self) {
+        self.stop_simulation_tx
+            .send(())
+            .expect("failed to stop simulation");
+    }
+}
+
+impl app::App for App {
+    fn event(&mut self, event: WindowEvent, context: Context, controller: LocalAppController) {
+        if let WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    logical_key: Key::Character(key),
+                    state: ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = &event
+        {
+            if key.eq_ignore_ascii_case("l") {
+                let linked = !self.split.is_linked();
+                self.split.set_linked(linked, context.borrow());
+                if linked {
+                    info!("linked the views to {}", self.split.focused().label);
+                } else {
+                    info!("unlinked the views");
+                }
+            }
+        }
+
+        if self.split.event(&event, context.borrow()) {
+            self.renderer.clear_trails();
+        }
+
+        if event == WindowEvent::CloseRequested {
+            controller.exit();
+        }
+    }
+
+    fn render(&mut self, target: &wgpu::SurfaceTexture, context: Context) -> Result<()> {
+        self.split.render(&self.renderer, target, context).ignore();
+        Ok(())
+    }
+
+    fn gui(&mut self, gui: &egui::Context, _context: Context) {
+        self.split.draw_labels(gui);
+    }
+}
@@ -1,17 +1,20 @@
 use std::sync::{mpsc, Arc};
 
 use clap::Parser;
-use color_eyre::eyre::{Ok, Result};
+use color_eyre::eyre::{Ok, Result, WrapErr};
 use futures::future::BoxFuture;
 use glam::Affine2;
-use image::ImageReader;
+use image::{GrayImage, ImageReader};
+use log::{error, info};
+use tokio::runtime::Runtime;
 use wgpu::{Features, Limits, SurfaceConfiguration};
 use winit::{dpi::LogicalSize, event::WindowEvent, window::WindowAttributes};
 
 use crate::{
-    app::{self, Context, LocalAppController, Run},
+    app::{self, AdapterCli, Context, ContextOptions, LocalAppController, Run},
     image::Evolver,
     map::Map,
+    profiler::Profiler,
     render::{Camera, Renderer},
     util::SyncingFuture,
 };
@@ -29,11 +32,23 @@ pub struct Cli {
 
     // #[arg(short = 'g', long, requires = "out")]
     // pub n_gens: Option<usize>,
+    /// Time the evolution stages and log the results once evolution is done.
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Evolve without a window, logging the best map set once done.
+    #[arg(long)]
+    pub headless: bool,
+
+    #[command(flatten)]
+    pub adapter: AdapterCli,
 }
 
 impl Cli {
     pub fn run(self) -> Result<()> {
-        Run::new(AppBuilder {
+        // not a subcommand of `crate::Cli` yet, so this is its own entry point
+        env_logger::init();
+        let app_builder = AppBuilder {
             generations: 10000,
             maps_per_set: 6,
             elite_len: 3,
@@ -42,16 +57,32 @@ impl Cli {
             n_points: 50000,
             mutation_strength: 1.0,
             mutation_damping: 0.02,
-        })
-        .with_window_attributes(
-            WindowAttributes::default().with_inner_size(LogicalSize::new(600, 600)),
-        )
-        .with_features(Features::PUSH_CONSTANTS)
-        .with_limits(Limits {
-            max_push_constant_size: 8,
-            ..Default::default()
-        })
-        .run()
+            profile: self.profile,
+        };
+        let options = self
+            .adapter
+            .apply(ContextOptions::default())
+            .with_features(Features::PUSH_CONSTANTS)
+            .with_optional_features(if self.profile {
+                Features::TIMESTAMP_QUERY
+            } else {
+                Features::empty()
+            })
+            .with_limits(Limits {
+                max_push_constant_size: 8,
+                ..Default::default()
+            });
+
+        if self.headless {
+            return app_builder.run_headless(options);
+        }
+
+        Run::new(app_builder)
+            .with_window_attributes(
+                WindowAttributes::default().with_inner_size(LogicalSize::new(600, 600)),
+            )
+            .with_context_options(options)
+            .run()
     }
 }
 
@@ -64,6 +95,7 @@ struct AppBuilder {
     n_points: usize,
     mutation_strength: f32,
     mutation_damping: f32,
+    profile: bool,
 }
 
 struct App {
@@ -73,6 +105,59 @@ struct App {
     best_map_set: mpsc::Receiver<Vec<Map>>,
 }
 
+impl AppBuilder {
+    fn run_headless(self, options: ContextOptions) -> Result<()> {
+        let runtime = Arc::new(Runtime::new()?);
+        let context = runtime.block_on(Context::headless(runtime.clone(), options))?;
+
+        let profiler = self
+            .profile
+            .then(|| Arc::new(Profiler::new(context.borrow())));
+        let evolver = self.evolver(&source_image()?, profiler.clone(), context.borrow())?;
+
+        let best_map_set = runtime.block_on(async {
+            evolver.evolve(self.generations, context.to_static()).await;
+            evolver.get_best_map_set(context.borrow()).await
+        })?;
+        if let Some(profiler) = profiler {
+            info!("{}", profiler.report());
+        }
+        info!("best map set: {best_map_set:#?}");
+        Ok(())
+    }
+
+    fn evolver(
+        &self,
+        image: &GrayImage,
+        profiler: Option<Arc<Profiler>>,
+        context: Context,
+    ) -> Result<Evolver> {
+        let mut evolver = Evolver::new(
+            image,
+            self.maps_per_set,
+            self.elite_len,
+            self.depth,
+            self.n_children,
+            self.n_points,
+            self.mutation_strength,
+            self.mutation_damping,
+            context,
+        )?;
+        evolver.set_profiler(profiler);
+        Ok(evolver)
+    }
+}
+
+const SOURCE_IMAGE: &str = "assets/source.png";
+
+fn source_image() -> Result<GrayImage> {
+    Ok(ImageReader::open(SOURCE_IMAGE)
+        .wrap_err_with(|| format!("failed to open {SOURCE_IMAGE}"))?
+        .decode()
+        .wrap_err_with(|| format!("failed to decode {SOURCE_IMAGE}"))?
+        .to_luma8())
+}
+
 impl app::AppBuilder for AppBuilder {
     type App = App;
 
@@ -81,33 +166,30 @@ impl app::AppBuilder for AppBuilder {
         surface_configuration: &SurfaceConfiguration,
         context: Context,
     ) -> BoxFuture<'static, Result<Self::App>> {
-        env_logger::init();
-        let context = context.into_static();
+        let app = self.build_app(surface_configuration, context.into_static());
+        Box::pin(async move { app })
+    }
+}
 
-        let image = ImageReader::open("assets/source.png")
-            .expect("failed to read source image")
-            .decode()
-            .expect("failed to decode source image")
-            .to_luma8();
+impl AppBuilder {
+    fn build_app(
+        self,
+        surface_configuration: &SurfaceConfiguration,
+        context: Context<'static>,
+    ) -> Result<App> {
+        let image = source_image()?;
 
         let (tx, rx) = mpsc::channel();
 
         let renderer = Renderer::new(context.borrow(), surface_configuration.format);
         let camera = Camera::new(Affine2::IDENTITY, context.borrow());
 
+        let profiler = self
+            .profile
+            .then(|| Arc::new(Profiler::new(context.borrow())));
         let evolver = Arc::new(
-            Evolver::new(
-                &image,
-                self.maps_per_set,
-                self.elite_len,
-                self.depth,
-                self.n_children,
-                self.n_points,
-                self.mutation_strength,
-                self.mutation_damping,
-                context.borrow(),
-            )
-            .expect("failed to create evolver"),
+            self.evolver(&image, profiler.clone(), context.borrow())
+                .wrap_err("failed to create evolver")?,
         );
 
         let evolver2 = evolver.clone();
@@ -117,18 +199,24 @@ impl app::AppBuilder for AppBuilder {
             evolver2
                 .evolve(generations, context2.borrow().to_static())
                 .await;
-            let best_map_set = evolver2.get_best_map_set(context2).await;
-            dbg!(&best_map_set);
-            tx.send(best_map_set).expect("failed to send message");
+            if let Some(profiler) = profiler {
+                info!("{}", profiler.report());
+            }
+            match evolver2.get_best_map_set(context2).await {
+                Result::Ok(best_map_set) => {
+                    info!("best map set: {best_map_set:?}");
+                    // the window may have been closed already
+                    tx.send(best_map_set).ok();
+                }
+                Err(error) => error!("failed to download the best map set: {error:?}"),
+            }
         });
 
-        Box::pin(async move {
-            Ok(App {
-                evolver,
-                renderer,
-                camera,
-                best_map_set: rx,
-            })
+        Ok(App {
+            evolver,
+            renderer,
+            camera,
+            best_map_set: rx,
         })
     }
 }
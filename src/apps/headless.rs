@@ -0,0 +1,113 @@
+use std::{path::PathBuf, sync::Arc};
+
+use clap::Parser;
+use color_eyre::eyre::Result;
+use log::{info, warn};
+use rand::{rngs::StdRng, SeedableRng};
+use tokio::runtime::Runtime;
+
+use crate::{
+    app::{AdapterCli, Context, ContextOptions, NoAdapter},
+    backend::{cpu::CpuBackend, render_image_tiled, Backend, GpuBackend},
+    map::*,
+    render::MapOverlay,
+    sim::{Escape, Point},
+};
+
+/// Renders an attractor to a PNG image without opening a window.
+#[derive(Debug, Clone, Parser)]
+pub struct Cli {
+    #[arg(short)]
+    pub n_points: usize,
+
+    /// Number of steps to take before rendering.
+    #[arg(short, long, default_value_t = 100)]
+    pub steps: usize,
+
+    /// Seed for the initial points, random by default. With `--cpu`, the same seed always gives
+    /// the same image.
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    #[arg(long, default_value_t = 1024)]
+    pub width: u32,
+
+    #[arg(long, default_value_t = 1024)]
+    pub height: u32,
+
+    #[arg(short, long)]
+    pub out: PathBuf,
+
+    /// How the region is fitted into the image when their aspect ratios differ.
+    #[arg(long, value_enum, default_value_t)]
+    pub aspect: AspectMode,
+
+    /// Outline the region and its image under each map.
+    #[arg(long, conflicts_with = "cpu")]
+    pub overlay: bool,
+
+    /// Simulate and render on the CPU instead of the GPU.
+    #[arg(long, conflicts_with_all = ["backends", "fallback_adapter"])]
+    pub cpu: bool,
+
+    #[command(flatten)]
+    pub adapter: AdapterCli,
+}
+
+impl Cli {
+    pub fn run(self) -> Result<()> {
+        let maps = Pentagon.maps();
+        let region = Pentagon.region();
+
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_rng(&mut rand::rng()),
+        };
+        let points: Vec<_> = (0..self.n_points)
+            .map(|_| Point::random_in(&region, &mut rng))
+            .collect();
+
+        let runtime = Arc::new(Runtime::new()?);
+        let mut backend: Box<dyn Backend> = if self.cpu {
+            Box::new(CpuBackend::new(points, &maps))
+        } else {
+            let options = self.adapter.apply(ContextOptions::default());
+            match runtime.block_on(Context::headless(runtime.clone(), options)) {
+                Ok(context) => {
+                    let mut backend = GpuBackend::new(&points, &maps, context);
+                    if self.overlay {
+                        let overlay = MapOverlay::new(region, maps.iter().copied().map(DMap::from));
+                        backend.set_overlay(Some(&overlay));
+                    }
+                    Box::new(backend)
+                }
+                Err(error) if error.is::<NoAdapter>() => {
+                    warn!("{error}, falling back to the CPU backend");
+                    if self.overlay {
+                        warn!("the CPU backend can't draw the overlay");
+                    }
+                    Box::new(CpuBackend::new(points, &maps))
+                }
+                Err(error) => return Err(error),
+            }
+        };
+        backend.set_escape(Escape::new(region));
+
+        let camera = region.to_clip_transform_for(self.width, self.height, self.aspect);
+        let image = runtime.block_on(async {
+            for _ in 0..self.steps {
+                backend.step().await;
+            }
+            render_image_tiled(&*backend, camera, self.width, self.height).await
+        })?;
+
+        image.save(&self.out)?;
+        info!(
+            "saved {}×{} image to {}",
+            self.width,
+            self.height,
+            self.out.display()
+        );
+        Ok(())
+    }
+}
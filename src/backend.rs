@@ -0,0 +1,197 @@
+use color_eyre::eyre::Result;
+use futures::{future::BoxFuture, FutureExt};
+use glam::{vec2, Affine2};
+use image::{GenericImage, RgbaImage};
+use itertools::Itertools;
+use wgpu::{BufferUsages, TextureFormat, COPY_BYTES_PER_ROW_ALIGNMENT};
+
+use crate::{
+    app::Context,
+    buffer::Buffer,
+    map::{DMap, Map},
+    render::{Camera, MapOverlay, Renderer},
+    sim::{Escape, Point, Precision, Simulation},
+};
+
+pub mod cpu;
+
+/// A chaos game simulation together with a way to rasterize it.
+///
+/// Implemented on the GPU by [`GpuBackend`] and on the CPU by [`cpu::CpuBackend`], which serves as
+/// a reference implementation and as a fallback when no adapter is available.
+pub trait Backend: Send {
+    fn set_maps(&mut self, maps: &[Map]) {
+        let maps = maps.iter().copied().map(DMap::from).collect_vec();
+        self.set_dmaps(&maps);
+    }
+
+    fn set_dmaps(&mut self, maps: &[DMap]);
+
+    fn set_escape(&mut self, escape: Escape);
+
+    fn step(&mut self) -> BoxFuture<'static, ()>;
+
+    fn transform_points(&mut self, transform: Affine2) -> BoxFuture<'static, ()>;
+
+    /// Changes the number of points, seeding new ones from the existing points. At least one
+    /// point is kept.
+    fn resize(&mut self, len: usize) -> BoxFuture<'static, ()>;
+
+    fn download_points(&self) -> BoxFuture<'static, Result<Vec<Point>>>;
+
+    /// Renders the points as white pixels over a black background, `camera` mapping clip space to
+    /// the simulation's coordinates.
+    fn render_image(
+        &self,
+        camera: Affine2,
+        width: u32,
+        height: u32,
+    ) -> BoxFuture<'static, Result<RgbaImage>>;
+
+    /// Largest width and height supported by [`Backend::render_image`].
+    fn max_image_dimension(&self) -> u32 {
+        u32::MAX
+    }
+}
+
+/// Same as [`Backend::render_image`], rendering one tile after the other if the image is larger
+/// than [`Backend::max_image_dimension`].
+pub async fn render_image_tiled<B: Backend + ?Sized>(
+    backend: &B,
+    camera: Affine2,
+    width: u32,
+    height: u32,
+) -> Result<RgbaImage> {
+    let max_dimension = backend.max_image_dimension();
+    if width <= max_dimension && height <= max_dimension {
+        return backend.render_image(camera, width, height).await;
+    }
+
+    let mut image = RgbaImage::new(width, height);
+    for y in (0..height).step_by(max_dimension as usize) {
+        for x in (0..width).step_by(max_dimension as usize) {
+            let tile_width = max_dimension.min(width - x);
+            let tile_height = max_dimension.min(height - y);
+            // from the clip space of the tile to that of the whole image
+            let tile_to_image = Affine2::from_scale_angle_translation(
+                vec2(
+                    tile_width as f32 / width as f32,
+                    tile_height as f32 / height as f32,
+                ),
+                0.0,
+                vec2(
+                    (2.0 * x as f32 + tile_width as f32) / width as f32 - 1.0,
+                    1.0 - (2.0 * y as f32 + tile_height as f32) / height as f32,
+                ),
+            );
+            let tile = backend
+                .render_image(camera * tile_to_image, tile_width, tile_height)
+                .await?;
+            image
+                .copy_from(&tile, x, y)
+                .expect("tile should fit in image");
+        }
+    }
+    Ok(image)
+}
+
+#[derive(Debug)]
+pub struct GpuBackend {
+    simulation: Simulation<Buffer<Point>>,
+    renderer: Renderer,
+    context: Context<'static>,
+}
+
+impl GpuBackend {
+    pub fn new(points: &[Point], maps: &[Map], context: Context) -> Self {
+        let maps = maps.iter().copied().map(DMap::from).collect_vec();
+        Self::with_precision(points, &maps, Precision::Single, context)
+    }
+
+    pub fn with_precision(
+        points: &[Point],
+        maps: &[DMap],
+        precision: Precision,
+        context: Context,
+    ) -> Self {
+        let point_buffer = Buffer::from_data(
+            points,
+            Some("Points"),
+            BufferUsages::STORAGE | BufferUsages::VERTEX | BufferUsages::COPY_SRC,
+            context.borrow(),
+        );
+
+        Self {
+            simulation: Simulation::with_precision(point_buffer, maps, precision, context.borrow()),
+            renderer: Renderer::new(context.borrow(), TextureFormat::Rgba8Unorm),
+            context: context.into_static(),
+        }
+    }
+
+    pub fn simulation(&self) -> &Simulation<Buffer<Point>> {
+        &self.simulation
+    }
+
+    /// Draws `overlay` into rendered images, or stops doing so if `None`.
+    pub fn set_overlay(&mut self, overlay: Option<&MapOverlay>) {
+        self.renderer.set_overlay(overlay, self.context.borrow());
+    }
+}
+
+impl Backend for GpuBackend {
+    fn set_dmaps(&mut self, maps: &[DMap]) {
+        self.simulation.set_dmaps(maps, self.context.borrow());
+    }
+
+    fn set_escape(&mut self, escape: Escape) {
+        self.simulation.set_escape(escape, self.context.borrow());
+    }
+
+    fn step(&mut self) -> BoxFuture<'static, ()> {
+        self.simulation.step(self.context.borrow()).boxed()
+    }
+
+    fn transform_points(&mut self, transform: Affine2) -> BoxFuture<'static, ()> {
+        self.simulation
+            .transform_points(transform, self.context.borrow())
+            .boxed()
+    }
+
+    fn resize(&mut self, len: usize) -> BoxFuture<'static, ()> {
+        self.simulation.resize(len, self.context.borrow()).boxed()
+    }
+
+    fn download_points(&self) -> BoxFuture<'static, Result<Vec<Point>>> {
+        self.simulation
+            .points()
+            .download(self.context.borrow())
+            .boxed()
+    }
+
+    fn render_image(
+        &self,
+        camera: Affine2,
+        width: u32,
+        height: u32,
+    ) -> BoxFuture<'static, Result<RgbaImage>> {
+        let camera = Camera::new(camera, self.context.borrow());
+        self.renderer
+            .render_to_image(
+                self.simulation.points(),
+                &camera,
+                width,
+                height,
+                self.context.borrow(),
+            )
+            .boxed()
+    }
+
+    fn max_image_dimension(&self) -> u32 {
+        let limits = self.context.device().limits();
+        // the texture is downloaded through a single buffer, with rows aligned to 64 pixels
+        let max_buffer_dimension = ((limits.max_buffer_size / 4) as f64).sqrt() as u32
+            / (COPY_BYTES_PER_ROW_ALIGNMENT / 4)
+            * (COPY_BYTES_PER_ROW_ALIGNMENT / 4);
+        limits.max_texture_dimension_2d.min(max_buffer_dimension)
+    }
+}
@@ -0,0 +1,386 @@
+use std::{
+    future,
+    sync::atomic::{AtomicU8, Ordering},
+    thread,
+};
+
+use color_eyre::eyre::Result;
+use futures::{future::BoxFuture, FutureExt};
+use glam::{vec2, Affine2, DAffine2, Vec2};
+use image::RgbaImage;
+
+use crate::{
+    backend::Backend,
+    map::{DMap, Map},
+    sim::{map_index_array, Escape, Point, Precision, Respawn},
+};
+
+/// Multithreaded CPU implementation of `sim.wgsl` and `render.wgsl`.
+///
+/// With [`Precision::Double`], it serves as the reference for `sim/f64.wgsl`.
+///
+/// Results only depend on the initial points and maps, not on the number of threads.
+#[derive(Debug, Clone)]
+pub struct CpuBackend {
+    points: Vec<Point>,
+    maps: Vec<DAffine2>,
+    map_indices: Vec<u32>,
+    precision: Precision,
+    escape: Escape,
+    step: u32,
+    respawn_count: u32,
+    n_threads: usize,
+}
+
+impl CpuBackend {
+    pub fn new(points: Vec<Point>, maps: &[Map]) -> Self {
+        let probability_weights: Vec<_> = maps.iter().map(|map| map.probability_weight).collect();
+        Self {
+            points,
+            maps: maps.iter().map(|map| map.map.as_daffine2()).collect(),
+            map_indices: map_index_array(&probability_weights),
+            precision: Precision::Single,
+            escape: Escape::default(),
+            step: 0,
+            respawn_count: 0,
+            n_threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    pub fn with_threads(mut self, n_threads: usize) -> Self {
+        self.n_threads = n_threads.max(1);
+        self
+    }
+
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    /// Number of points respawned during the last step.
+    pub fn respawn_count(&self) -> u32 {
+        self.respawn_count
+    }
+
+    /// Advances every point by one step, synchronously.
+    pub fn step_blocking(&mut self) {
+        self.step += 1;
+
+        let previous = &self.points;
+        let mut next = previous.clone();
+        let chunk_len = previous.len().div_ceil(self.n_threads).max(1);
+
+        let stepper = Stepper {
+            previous,
+            maps: &self.maps,
+            precision: self.precision,
+            map_indices: &self.map_indices,
+            escape: &self.escape,
+            seed: self.step,
+        };
+
+        self.respawn_count = thread::scope(|scope| {
+            let handles: Vec<_> = next
+                .chunks_mut(chunk_len)
+                .enumerate()
+                .map(|(chunk_idx, chunk)| {
+                    let stepper = &stepper;
+                    scope.spawn(move || {
+                        let mut respawn_count = 0;
+                        for (idx, point) in chunk.iter_mut().enumerate() {
+                            let respawned;
+                            (*point, respawned) = stepper.step(chunk_idx * chunk_len + idx);
+                            respawn_count += u32::from(respawned);
+                        }
+                        respawn_count
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("simulation thread panicked"))
+                .sum()
+        });
+
+        self.points = next;
+    }
+
+    /// Rasterizes the points synchronously, one pixel per point.
+    pub fn render_blocking(&self, camera: Affine2, width: u32, height: u32) -> RgbaImage {
+        let inverse_camera = camera.inverse();
+        let coverage: Vec<AtomicU8> = (0..width * height).map(|_| AtomicU8::new(0)).collect();
+        let chunk_len = self.points.len().div_ceil(self.n_threads).max(1);
+
+        thread::scope(|scope| {
+            for chunk in self.points.chunks(chunk_len) {
+                let coverage = &coverage;
+                scope.spawn(move || {
+                    for point in chunk {
+                        let clip = inverse_camera.transform_point2(point.position);
+                        // clip space has y pointing up, framebuffers have it pointing down
+                        let pixel =
+                            (vec2(clip.x, -clip.y) + 1.0) * 0.5 * vec2(width as f32, height as f32);
+                        if pixel.x >= 0.0
+                            && pixel.y >= 0.0
+                            && pixel.x < width as f32
+                            && pixel.y < height as f32
+                        {
+                            let idx = pixel.y as u32 * width + pixel.x as u32;
+                            coverage[idx as usize].store(u8::MAX, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        });
+
+        let bytes = coverage
+            .into_iter()
+            .flat_map(|value| {
+                let value = value.into_inner();
+                [value, value, value, u8::MAX]
+            })
+            .collect();
+        RgbaImage::from_vec(width, height, bytes).expect("failed to create image")
+    }
+}
+
+impl Backend for CpuBackend {
+    fn set_dmaps(&mut self, maps: &[DMap]) {
+        let probability_weights: Vec<_> = maps.iter().map(|map| map.probability_weight).collect();
+        self.maps = maps.iter().map(|map| map.map).collect();
+        self.map_indices = map_index_array(&probability_weights);
+    }
+
+    fn set_escape(&mut self, escape: Escape) {
+        self.escape = escape;
+    }
+
+    fn step(&mut self) -> BoxFuture<'static, ()> {
+        self.step_blocking();
+        future::ready(()).boxed()
+    }
+
+    fn transform_points(&mut self, transform: Affine2) -> BoxFuture<'static, ()> {
+        for point in &mut self.points {
+            point.position = transform.transform_point2(point.position);
+        }
+        future::ready(()).boxed()
+    }
+
+    /// Mirrors `seed_points` in `sim/seed.wgsl`.
+    fn resize(&mut self, len: usize) -> BoxFuture<'static, ()> {
+        let len = len.max(1);
+        let kept = self.points.len().min(len);
+        let region = self.escape.region;
+        let extent = region.max - region.min;
+        let seeded = (0..(len - kept) as u32)
+            .map(|idx| {
+                let mut state = pcg(self.step ^ pcg(idx));
+                state = pcg(state);
+                let x = to_unit_float(state);
+                state = pcg(state);
+                let y = to_unit_float(state);
+                let unit = vec2(x, y);
+
+                let position = if kept == 0 {
+                    region.min + unit * extent
+                } else {
+                    state = pcg(state);
+                    let source = self.points[state as usize % self.points.len()].position;
+                    source + (unit - 0.5) * extent * 1.0e-6
+                };
+                Point { position }
+            })
+            .collect::<Vec<_>>();
+
+        self.points.truncate(kept);
+        self.points.extend(seeded);
+        future::ready(()).boxed()
+    }
+
+    fn download_points(&self) -> BoxFuture<'static, Result<Vec<Point>>> {
+        future::ready(Ok(self.points.clone())).boxed()
+    }
+
+    fn render_image(
+        &self,
+        camera: Affine2,
+        width: u32,
+        height: u32,
+    ) -> BoxFuture<'static, Result<RgbaImage>> {
+        future::ready(Ok(self.render_blocking(camera, width, height))).boxed()
+    }
+}
+
+struct Stepper<'a> {
+    previous: &'a [Point],
+    maps: &'a [DAffine2],
+    precision: Precision,
+    map_indices: &'a [u32],
+    escape: &'a Escape,
+    seed: u32,
+}
+
+impl Stepper<'_> {
+    /// Mirrors `step_sim` in `sim.wgsl`.
+    fn step(&self, idx: usize) -> (Point, bool) {
+        let point = self.previous[idx].position;
+        let hash = (point.x + point.y).to_bits();
+        let map = self.maps[self.map_indices[hash as usize % self.map_indices.len()] as usize];
+        let next = match self.precision {
+            Precision::Single => {
+                let map = map.as_affine2();
+                map.matrix2.x_axis * point.x + map.matrix2.y_axis * point.y + map.translation
+            }
+            Precision::Double => {
+                let point = point.as_dvec2();
+                (map.matrix2.x_axis * point.x + map.matrix2.y_axis * point.y + map.translation)
+                    .as_vec2()
+            }
+        };
+
+        if self.has_escaped(next) {
+            (
+                Point {
+                    position: self.respawn(idx as u32),
+                },
+                true,
+            )
+        } else {
+            (Point { position: next }, false)
+        }
+    }
+
+    fn has_escaped(&self, point: Vec2) -> bool {
+        let center = 0.5 * (self.escape.region.min + self.escape.region.max);
+        !point.is_finite() || point.distance(center) > self.escape.radius
+    }
+
+    // unlike the shader, live points are taken from the previous step to stay deterministic
+    fn respawn(&self, idx: u32) -> Vec2 {
+        let Escape {
+            region, respawn, ..
+        } = *self.escape;
+        let mut state = pcg(self.seed ^ pcg(idx));
+        let extent = region.max - region.min;
+
+        if respawn == Respawn::LivePoint {
+            state = pcg(state);
+            let other = self.previous[state as usize % self.previous.len()].position;
+            if !self.has_escaped(other) {
+                state = pcg(state);
+                let x = to_unit_float(state);
+                state = pcg(state);
+                let y = to_unit_float(state);
+                return other + (vec2(x, y) - 0.5) * extent * 1.0e-6;
+            }
+        }
+
+        state = pcg(state);
+        let x = to_unit_float(state);
+        state = pcg(state);
+        let y = to_unit_float(state);
+        region.min + vec2(x, y) * extent
+    }
+}
+
+fn pcg(value: u32) -> u32 {
+    let state = value.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+fn to_unit_float(value: u32) -> f32 {
+    (value >> 8) as f32 / 16777216.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::Path};
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::map::{Maps, Pentagon};
+
+    const GOLDEN_IMAGE: &str = "assets/golden/pentagon.png";
+
+    fn pentagon(n_threads: usize) -> CpuBackend {
+        let region = Pentagon.region();
+        let mut rng = StdRng::seed_from_u64(0);
+        let points = (0..10_000)
+            .map(|_| Point::random_in(&region, &mut rng))
+            .collect();
+        let mut backend = CpuBackend::new(points, &Pentagon.maps()).with_threads(n_threads);
+        backend.set_escape(Escape::new(region));
+        for _ in 0..20 {
+            backend.step_blocking();
+        }
+        backend
+    }
+
+    fn camera() -> Affine2 {
+        Pentagon.region().to_clip_transform()
+    }
+
+    #[test]
+    fn independent_of_threads() {
+        let single = pentagon(1);
+        let multi = pentagon(8);
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(single.points()),
+            bytemuck::cast_slice::<_, u8>(multi.points()),
+        );
+        assert_eq!(
+            single.render_blocking(camera(), 64, 64),
+            multi.render_blocking(camera(), 64, 64),
+        );
+    }
+
+    #[test]
+    fn renders_points_as_pixels() {
+        let positions = [
+            vec2(-1.0, 1.0),
+            vec2(0.0, 0.0),
+            vec2(0.75, -0.75),
+            vec2(2.0, 0.0),
+        ];
+        let backend = CpuBackend::new(
+            positions.map(|position| Point { position }).to_vec(),
+            &Pentagon.maps(),
+        );
+        let image = futures::executor::block_on(backend.render_image(Affine2::IDENTITY, 4, 4))
+            .expect("rendering on the CPU can't fail");
+
+        let rows: Vec<String> = image
+            .rows()
+            .map(|row| {
+                row.map(|pixel| match pixel.0 {
+                    [u8::MAX, u8::MAX, u8::MAX, u8::MAX] => '#',
+                    [0, 0, 0, u8::MAX] => '.',
+                    _ => '?',
+                })
+                .collect()
+            })
+            .collect();
+        assert_eq!(rows, ["#...", "....", "..#.", "...#"]);
+    }
+
+    /// Set `NEPHOS_BLESS` to overwrite the golden image after intended changes.
+    #[test]
+    fn matches_golden_image() {
+        let image = pentagon(4).render_blocking(camera(), 64, 64);
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_IMAGE);
+        if env::var_os("NEPHOS_BLESS").is_some() {
+            image.save(&path).expect("failed to save golden image");
+        }
+        let golden = image::open(&path)
+            .expect("failed to open golden image")
+            .into_rgba8();
+        assert!(image == golden, "differs from {GOLDEN_IMAGE}");
+    }
+}
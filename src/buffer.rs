@@ -1,6 +1,7 @@
 use std::{future::Future, iter, marker::PhantomData, mem, ops::Deref};
 
 use bytemuck::Pod;
+use color_eyre::eyre::{Result, WrapErr};
 use wgpu::{util::BufferInitDescriptor, BufferDescriptor, BufferUsages, CommandEncoderDescriptor};
 use wgpu_async::AsyncBuffer;
 
@@ -61,11 +62,19 @@ impl<T: Pod> Buffer<T> {
         self.len() as u32
     }
 
+    pub fn write(&self, offset: usize, data: &[T], context: Context) {
+        context.queue().write_buffer(
+            self,
+            (offset * mem::size_of::<T>()) as u64,
+            bytemuck::cast_slice(data),
+        );
+    }
+
     pub fn as_untyped(&self) -> &UntypedBuffer {
         &self.untyped
     }
 
-    pub fn download(&self, context: Context) -> impl Future<Output = Vec<T>> + 'static {
+    pub fn download(&self, context: Context) -> impl Future<Output = Result<Vec<T>>> + 'static {
         let context = context.into_static();
 
         let read_buffer = context.device().create_buffer(&BufferDescriptor {
@@ -87,13 +96,13 @@ impl<T: Pod> Buffer<T> {
             slice
                 .map_async(wgpu::MapMode::Read)
                 .await
-                .expect("failed to map buffer");
+                .wrap_err("failed to map buffer for reading")?;
             let map_data = {
                 let mapped_range = slice.get_mapped_range();
                 bytemuck::cast_slice(&mapped_range).to_vec()
             };
             read_buffer.unmap();
-            map_data
+            Ok(map_data)
         }
     }
 }
@@ -0,0 +1,218 @@
+use std::f64;
+
+use glam::{dvec2, DAffine2, DVec2};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{MouseButton, MouseScrollDelta, WindowEvent},
+};
+
+use crate::{
+    controls::{Action, KeyBindings},
+    map::{AspectMode, Rect},
+};
+
+/// Pans, zooms and rotates a camera from window events.
+///
+/// The transform maps clip space to world coordinates, like [`Rect::to_clip_transform_for`], and
+/// follows the aspect ratio of the window as it is resized.
+///
+/// - drag with the left mouse button to pan
+/// - scroll to zoom about the cursor
+/// - keys bound to the camera actions of [`KeyBindings`], by default `PageUp` and `PageDown` to
+///   zoom about the centre, `Q` and `E` to rotate, arrow keys to pan and `Home` to reset to the
+///   region
+#[derive(Debug, Clone)]
+pub struct CameraController {
+    // from the square space of `AspectMode::clip_scale` to world coordinates
+    view: DAffine2,
+    region: Rect,
+    mode: AspectMode,
+    size: DVec2,
+    cursor: Option<DVec2>,
+    dragging: bool,
+    bindings: KeyBindings,
+}
+
+impl CameraController {
+    /// Zoom factor of one scroll wheel line or key press.
+    const ZOOM_STEP: f64 = 1.1;
+    const ROTATE_STEP: f64 = f64::consts::PI / 36.0;
+    /// Distance panned by an arrow key press, a twentieth of the view.
+    const PAN_STEP: f64 = 0.1;
+    /// Pixels of a touchpad scroll that count as one line.
+    const PIXELS_PER_LINE: f64 = 40.0;
+
+    /// Starts out showing `region` in a window of `size`.
+    pub fn new(region: Rect, mode: AspectMode, size: PhysicalSize<u32>) -> Self {
+        let mut controller = Self {
+            view: DAffine2::IDENTITY,
+            region,
+            mode,
+            size: dvec2(size.width.into(), size.height.into()),
+            cursor: None,
+            dragging: false,
+            bindings: KeyBindings::default(),
+        };
+        controller.reset();
+        controller
+    }
+
+    pub fn with_key_bindings(mut self, bindings: KeyBindings) -> Self {
+        self.bindings = bindings;
+        self
+    }
+
+    /// The camera of the window.
+    pub fn transform(&self) -> DAffine2 {
+        self.view * DAffine2::from_scale(self.clip_scale(self.size))
+    }
+
+    /// The same view for a target of another size, e.g. an offscreen texture.
+    pub fn transform_for(&self, size: PhysicalSize<u32>) -> DAffine2 {
+        self.view
+            * DAffine2::from_scale(self.clip_scale(dvec2(size.width.into(), size.height.into())))
+    }
+
+    /// Moves the camera of the window to `transform`, e.g. one saved earlier.
+    pub fn set_transform(&mut self, transform: DAffine2) {
+        self.view = transform * DAffine2::from_scale(self.clip_scale(self.size).recip());
+    }
+
+    /// The region that [`CameraController::reset`] goes back to.
+    pub fn set_region(&mut self, region: Rect) {
+        self.region = region;
+    }
+
+    pub fn set_aspect_mode(&mut self, mode: AspectMode) {
+        let transform = self.transform();
+        self.mode = mode;
+        self.set_transform(transform);
+    }
+
+    pub fn reset(&mut self) {
+        let size = self.size.max(DVec2::ONE).as_uvec2();
+        self.set_transform(
+            self.region
+                .to_clip_transform_for(size.x, size.y, self.mode)
+                .as_daffine2(),
+        );
+    }
+
+    /// Whether a drag is in progress, in which case the view will keep changing.
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+
+    /// Moves the view by `delta` in the square space of [`AspectMode::clip_scale`].
+    pub fn pan(&mut self, delta: DVec2) {
+        self.view *= DAffine2::from_translation(delta);
+    }
+
+    /// Scales the view by `factor` about `center` in the square space of
+    /// [`AspectMode::clip_scale`], zooming in if `factor < 1`.
+    pub fn zoom(&mut self, factor: f64, center: DVec2) {
+        self.view *= DAffine2::from_translation(center)
+            * DAffine2::from_scale(DVec2::splat(factor))
+            * DAffine2::from_translation(-center);
+    }
+
+    /// Rotates the view counterclockwise by `angle` radians about its centre.
+    pub fn rotate(&mut self, angle: f64) {
+        self.view *= DAffine2::from_angle(angle);
+    }
+
+    /// Updates the view, returning whether it changed.
+    pub fn event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::Resized(size) => {
+                // keep the view, only revealing or hiding its sides
+                self.size = dvec2(size.width.into(), size.height.into());
+                true
+            }
+
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = self.to_view(*position);
+                let previous = self.cursor.replace(cursor);
+                match previous {
+                    Some(previous) if self.dragging => {
+                        self.pan(previous - cursor);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                // the button may be released outside of the window
+                let was_dragging = self.dragging;
+                self.dragging = false;
+                was_dragging
+            }
+
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                let was_dragging = self.dragging;
+                self.dragging = state.is_pressed();
+                // report the end of a drag, after which the view settles
+                was_dragging && !self.dragging
+            }
+
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => f64::from(*y),
+                    MouseScrollDelta::PixelDelta(PhysicalPosition { y, .. }) => {
+                        y / Self::PIXELS_PER_LINE
+                    }
+                };
+                self.zoom(
+                    Self::ZOOM_STEP.powf(-lines),
+                    self.cursor.unwrap_or(DVec2::ZERO),
+                );
+                true
+            }
+
+            WindowEvent::KeyboardInput { .. } => self
+                .bindings
+                .action(event)
+                .is_some_and(|action| self.action(action)),
+
+            _ => false,
+        }
+    }
+
+    /// Applies `action` if it moves the camera, returning whether it did.
+    pub fn action(&mut self, action: Action) -> bool {
+        match action {
+            Action::ZoomIn => self.zoom(Self::ZOOM_STEP.recip(), DVec2::ZERO),
+            Action::ZoomOut => self.zoom(Self::ZOOM_STEP, DVec2::ZERO),
+            Action::RotateLeft => self.rotate(Self::ROTATE_STEP),
+            Action::RotateRight => self.rotate(-Self::ROTATE_STEP),
+            Action::PanLeft => self.pan(dvec2(-Self::PAN_STEP, 0.0)),
+            Action::PanRight => self.pan(dvec2(Self::PAN_STEP, 0.0)),
+            Action::PanUp => self.pan(dvec2(0.0, Self::PAN_STEP)),
+            Action::PanDown => self.pan(dvec2(0.0, -Self::PAN_STEP)),
+            Action::ResetView => self.reset(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn clip_scale(&self, size: DVec2) -> DVec2 {
+        let size = size.max(DVec2::ONE);
+        self.mode.clip_scale(size.x / size.y)
+    }
+
+    // in the square space of `AspectMode::clip_scale`
+    fn to_view(&self, position: PhysicalPosition<f64>) -> DVec2 {
+        let size = self.size.max(DVec2::ONE);
+        let clip = dvec2(
+            2.0 * position.x / size.x - 1.0,
+            1.0 - 2.0 * position.y / size.y,
+        );
+        clip * self.clip_scale(self.size)
+    }
+}
@@ -0,0 +1,252 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use clap::ValueEnum;
+use itertools::Itertools;
+use thiserror::Error;
+use winit::{
+    event::{ElementState, KeyEvent, WindowEvent},
+    keyboard::{Key, NamedKey, SmolStr},
+};
+
+/// Something a key can be bound to, see [`KeyBindings`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, ValueEnum)]
+pub enum Action {
+    /// Pause or resume the simulation.
+    Pause,
+    /// Advance the simulation by one step.
+    Step,
+    /// Scatter the points at random over the region.
+    Reset,
+    /// Pick a new random seed for points respawned after escaping or scattered by `reset`.
+    Reseed,
+    /// Shorten the interval between steps.
+    Faster,
+    /// Lengthen the interval between steps.
+    Slower,
+    /// Zoom in about the centre of the view.
+    ZoomIn,
+    /// Zoom out about the centre of the view.
+    ZoomOut,
+    /// Rotate the view counterclockwise.
+    RotateLeft,
+    /// Rotate the view clockwise.
+    RotateRight,
+    /// Move the view left.
+    PanLeft,
+    /// Move the view right.
+    PanRight,
+    /// Move the view up.
+    PanUp,
+    /// Move the view down.
+    PanDown,
+    /// Go back to showing the whole region.
+    ResetView,
+    /// Show or hide the outlines of the maps.
+    Overlay,
+    /// Cycle through the built-in palettes.
+    Palette,
+    /// Show or hide the key bindings.
+    Help,
+}
+
+impl Action {
+    pub fn description(self) -> String {
+        let value = self
+            .to_possible_value()
+            .expect("all actions have a possible value");
+        match value.get_help() {
+            Some(help) => help.to_string().trim_end_matches('.').to_owned(),
+            None => value.get_name().replace('-', " "),
+        }
+    }
+}
+
+/// Named keys that can be bound by name, e.g. `space` or `pageup`.
+const NAMED_KEYS: [NamedKey; 27] = [
+    NamedKey::Space,
+    NamedKey::Enter,
+    NamedKey::Tab,
+    NamedKey::Escape,
+    NamedKey::Backspace,
+    NamedKey::Delete,
+    NamedKey::Insert,
+    NamedKey::Home,
+    NamedKey::End,
+    NamedKey::PageUp,
+    NamedKey::PageDown,
+    NamedKey::ArrowLeft,
+    NamedKey::ArrowRight,
+    NamedKey::ArrowUp,
+    NamedKey::ArrowDown,
+    NamedKey::F1,
+    NamedKey::F2,
+    NamedKey::F3,
+    NamedKey::F4,
+    NamedKey::F5,
+    NamedKey::F6,
+    NamedKey::F7,
+    NamedKey::F8,
+    NamedKey::F9,
+    NamedKey::F10,
+    NamedKey::F11,
+    NamedKey::F12,
+];
+
+#[derive(Debug, Clone, Error)]
+pub enum ParseBindingError {
+    #[error("expected `ACTION=KEY`, e.g. `pause=p`")]
+    Format,
+    #[error("unknown action `{0}`")]
+    Action(String),
+    #[error(
+        "unknown key `{0}`, expected a single character or a name such as `space` or `pageup`"
+    )]
+    Key(String),
+}
+
+/// A key, as bound in [`KeyBindings`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BoundKey(Key);
+
+impl BoundKey {
+    // characters are matched regardless of case
+    fn new(key: &Key) -> Self {
+        match key {
+            Key::Character(c) => Self(Key::Character(SmolStr::new(c.to_lowercase()))),
+            key => Self(key.clone()),
+        }
+    }
+}
+
+impl FromStr for BoundKey {
+    type Err = ParseBindingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.chars().count() == 1 {
+            return Ok(Self::new(&Key::Character(SmolStr::new(s))));
+        }
+        NAMED_KEYS
+            .into_iter()
+            .find(|named| format!("{named:?}").eq_ignore_ascii_case(s))
+            .map(|named| Self(Key::Named(named)))
+            .ok_or_else(|| ParseBindingError::Key(s.to_owned()))
+    }
+}
+
+impl fmt::Display for BoundKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Key::Character(c) => write!(f, "{}", c.to_uppercase()),
+            Key::Named(named) => write!(f, "{named:?}"),
+            key => write!(f, "{key:?}"),
+        }
+    }
+}
+
+/// A key bound to an action, written as `ACTION=KEY`, e.g. `pause=p` or `zoom-in=pageup`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub action: Action,
+    pub key: BoundKey,
+}
+
+impl FromStr for KeyBinding {
+    type Err = ParseBindingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (action, key) = s.split_once('=').ok_or(ParseBindingError::Format)?;
+        Ok(Self {
+            action: Action::from_str(action.trim(), true)
+                .map_err(|_| ParseBindingError::Action(action.to_owned()))?,
+            key: key.trim().parse()?,
+        })
+    }
+}
+
+/// Which key triggers which [`Action`].
+///
+/// By default `+`, `-` and `R` change the step interval and reset the points, so the camera
+/// zooms with `PageUp` and `PageDown` and resets to the region with `Home`, rather than with
+/// `+`, `-` and `R` as it did before the simulation had key bindings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings {
+    bindings: HashMap<BoundKey, Action>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let character = |c: &str| BoundKey::new(&Key::Character(SmolStr::new(c)));
+        let named = |named| BoundKey(Key::Named(named));
+        Self {
+            bindings: HashMap::from([
+                (named(NamedKey::Space), Action::Pause),
+                (character("n"), Action::Step),
+                (character("r"), Action::Reset),
+                (character("s"), Action::Reseed),
+                (character("+"), Action::Faster),
+                (character("="), Action::Faster),
+                (character("-"), Action::Slower),
+                (named(NamedKey::PageUp), Action::ZoomIn),
+                (named(NamedKey::PageDown), Action::ZoomOut),
+                (character("q"), Action::RotateLeft),
+                (character("e"), Action::RotateRight),
+                (named(NamedKey::ArrowLeft), Action::PanLeft),
+                (named(NamedKey::ArrowRight), Action::PanRight),
+                (named(NamedKey::ArrowUp), Action::PanUp),
+                (named(NamedKey::ArrowDown), Action::PanDown),
+                (named(NamedKey::Home), Action::ResetView),
+                (character("o"), Action::Overlay),
+                (character("p"), Action::Palette),
+                (character("h"), Action::Help),
+                (named(NamedKey::F1), Action::Help),
+            ]),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Replaces the default keys of each action in `bindings`.
+    pub fn with_overrides<'a>(
+        mut self,
+        bindings: impl IntoIterator<Item = &'a KeyBinding>,
+    ) -> Self {
+        let bindings = bindings.into_iter().collect_vec();
+        self.bindings
+            .retain(|_, action| !bindings.iter().any(|binding| binding.action == *action));
+        for binding in bindings {
+            self.bindings.insert(binding.key.clone(), binding.action);
+        }
+        self
+    }
+
+    /// The action bound to a key pressed in `event`, if any.
+    pub fn action(&self, event: &WindowEvent) -> Option<Action> {
+        match event {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key,
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.bindings.get(&BoundKey::new(logical_key)).copied(),
+            _ => None,
+        }
+    }
+
+    /// The keys bound to each action that has any, e.g. for a help overlay.
+    pub fn keys(&self) -> Vec<(Action, Vec<&BoundKey>)> {
+        self.bindings
+            .iter()
+            .map(|(key, action)| (*action, key))
+            .into_group_map()
+            .into_iter()
+            .map(|(action, mut keys)| {
+                keys.sort_by_key(|key| key.to_string());
+                (action, keys)
+            })
+            .sorted_by_key(|(action, _)| *action)
+            .collect()
+    }
+}
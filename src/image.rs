@@ -9,6 +9,7 @@ use std::{
         atomic::{AtomicUsize, Ordering},
         Arc, Mutex,
     },
+    time::Instant,
 };
 
 use bytemuck::{Pod, Zeroable};
@@ -17,7 +18,7 @@ use futures::FutureExt;
 use glam::{vec2, Affine2, Mat2, Mat3, Vec2};
 use image::{GrayImage, RgbaImage};
 use itertools::Itertools;
-use log::info;
+use log::{debug, error, info};
 use rand::Rng;
 use wgpu::*;
 use wgsl_preprocessor::ShaderBuilder;
@@ -26,6 +27,7 @@ use crate::{
     app::Context,
     buffer::Buffer,
     map::Map,
+    profiler::{ProfileScope, Profiler},
     render::{Camera, Renderer},
     sim::Point,
     util::{mat2, SyncingFuture, WgpuMat3x3},
@@ -91,6 +93,7 @@ pub struct Evolver {
     camera: Camera,
     rate: Rate,
     select: Select,
+    profiler: Option<Arc<Profiler>>,
 }
 
 impl Evolver {
@@ -214,6 +217,7 @@ impl Evolver {
             camera,
             rate,
             select: sort,
+            profiler: None,
         })
     }
 
@@ -231,9 +235,18 @@ impl Evolver {
         for i in 0..generations {
             info!("Generation {}...", i + 1);
             self.step(context.borrow()).await;
+            if let Some(profiler) = &self.profiler {
+                profiler.resolve(context.borrow()).await;
+            }
         }
     }
 
+    /// Times the simulation, rendering, rating and selection stages with `profiler`.
+    pub fn set_profiler(&mut self, profiler: Option<Arc<Profiler>>) {
+        self.renderer.set_profiler(profiler.clone());
+        self.profiler = profiler;
+    }
+
     pub fn reset_simulations(&self, context: Context) -> impl SyncingFuture {
         self.simulate.reset(&self.random_points, context)
     }
@@ -242,6 +255,7 @@ impl Evolver {
         self.simulate.step_simulations(
             self.maps_per_set,
             self.step.load(Ordering::Relaxed),
+            self.profiler.as_deref(),
             context,
         )
     }
@@ -256,11 +270,13 @@ impl Evolver {
     }
 
     pub fn compare(&self, context: Context) -> impl SyncingFuture {
-        self.rate.compare_all(context.borrow())
+        self.rate
+            .compare_all(self.profiler.as_deref(), context.borrow())
     }
 
     pub fn reduce(&self, context: Context) -> impl SyncingFuture {
-        self.rate.reduce_all(context.borrow())
+        self.rate
+            .reduce_all(self.profiler.as_deref(), context.borrow())
     }
 
     pub fn select_simulations(&self, context: Context) -> impl Future<Output = ()> + 'static {
@@ -280,21 +296,33 @@ impl Evolver {
         let mutation_strength = self.mutation_strength
             * f32::exp(-(self.step.load(Ordering::Relaxed) as f32 * self.mutation_damping));
 
-        self.select.select(
-            self.elite_len,
-            self.maps_per_set,
-            self.n_children,
-            mutation_strength,
-            self.maps.clone(),
-            intermediate_buffers,
-            context,
-        )
+        let start = Instant::now();
+        let profiler = self.profiler.clone();
+        self.select
+            .select(
+                self.elite_len,
+                self.maps_per_set,
+                self.n_children,
+                mutation_strength,
+                self.maps.clone(),
+                intermediate_buffers,
+                context,
+            )
+            .map(move |()| {
+                // selection happens mostly on the CPU, so it's only timed by the wall clock
+                if let Some(profiler) = profiler {
+                    profiler.record("Evolution Select", start.elapsed());
+                }
+            })
     }
 
     pub fn debug_maps(&self, context: Context) -> impl Future<Output = ()> + 'static {
         let data_fut = self.maps.download(context);
         async move {
-            dbg!(data_fut.await);
+            match data_fut.await {
+                Result::Ok(maps) => debug!("maps: {maps:?}"),
+                Err(error) => error!("failed to download the maps: {error:?}"),
+            }
         }
     }
 
@@ -305,11 +333,14 @@ impl Evolver {
     pub fn debug_first_points(&self, context: Context) -> impl Future<Output = ()> + 'static {
         let data_fut = self.simulate.point_buffers[0].0.download(context);
         async move {
-            dbg!(data_fut.await);
+            match data_fut.await {
+                Result::Ok(points) => debug!("first points: {points:?}"),
+                Err(error) => error!("failed to download the first points: {error:?}"),
+            }
         }
     }
 
-    pub fn get_first_buffer_a(&self, context: Context) -> impl Future<Output = Vec<u32>> {
+    pub fn get_first_buffer_a(&self, context: Context) -> impl Future<Output = Result<Vec<u32>>> {
         self.rate.map_set_data[0]
             .reduce_buffer_pair
             .lock()
@@ -318,7 +349,9 @@ impl Evolver {
             .download(context)
             .then(|buf| {
                 future::ready({
-                    dbg!(buf.len());
+                    if let Result::Ok(buf) = &buf {
+                        debug!("first buffer a has {} elements", buf.len());
+                    }
                     buf
                 })
             })
@@ -327,7 +360,10 @@ impl Evolver {
     pub fn debug_scores(&self, context: Context) -> impl Future<Output = ()> + 'static {
         let data_fut = self.select.scores.download(context);
         async move {
-            dbg!(data_fut.await);
+            match data_fut.await {
+                Result::Ok(scores) => debug!("scores: {scores:?}"),
+                Err(error) => error!("failed to download the scores: {error:?}"),
+            }
         }
     }
 
@@ -335,11 +371,14 @@ impl Evolver {
         &self.simulate.point_buffers[0].0
     }
 
-    pub fn get_best_map_set(&self, context: Context) -> impl Future<Output = Vec<Map>> + 'static {
+    pub fn get_best_map_set(
+        &self,
+        context: Context,
+    ) -> impl Future<Output = Result<Vec<Map>>> + 'static {
         let fut = self.maps.download(context);
         let maps_per_set = self.maps_per_set;
         async move {
-            fut.await[0..maps_per_set]
+            Ok(fut.await?[0..maps_per_set]
                 .iter()
                 .map(|affine| {
                     let mat3 = affine.computed.into();
@@ -349,7 +388,7 @@ impl Evolver {
                         probability_weight: 1.0,
                     }
                 })
-                .collect_vec()
+                .collect_vec())
         }
     }
 }
@@ -597,8 +636,11 @@ impl Simulate {
         &self,
         maps_per_set: usize,
         step: usize,
+        profiler: Option<&Profiler>,
         context: Context,
     ) -> impl SyncingFuture {
+        let scope = ProfileScope::maybe(profiler, "Evolution Simulate");
+        let n_simulations = self.point_buffers.len();
         let commands = self.point_buffers.iter().enumerate().map(
             |(idx, (point_buffer, point_bind_group, ..))| {
                 let mut encoder =
@@ -612,7 +654,7 @@ impl Simulate {
                         label: Some(&format!(
                             "Evolution Simulation #{idx} (step #{step}) Compute Pass",
                         )),
-                        timestamp_writes: None,
+                        timestamp_writes: scope.compute_pass(idx, n_simulations),
                     });
                     compute_pass.set_pipeline(&self.simulate_pipeline);
                     compute_pass
@@ -625,14 +667,16 @@ impl Simulate {
                 encoder.finish()
             },
         );
-        context.queue().submit(commands)
+        let submitted = context.queue().submit(commands);
+        scope.submitted(context);
+        submitted
     }
 
     fn render(&self, renderer: &Renderer, camera: &Camera, context: Context) -> impl SyncingFuture {
         renderer.render_all(
             self.point_buffers
                 .iter()
-                .map(|(buffer, .., texture_view)| (buffer, camera, texture_view)),
+                .map(|(buffer, _, texture, _)| (buffer, camera, texture)),
             context,
         )
     }
@@ -968,7 +1012,9 @@ impl Rate {
         })
     }
 
-    fn compare_all(&self, context: Context) -> impl SyncingFuture {
+    fn compare_all(&self, profiler: Option<&Profiler>, context: Context) -> impl SyncingFuture {
+        let scope = ProfileScope::maybe(profiler, "Evolution Rate Compare");
+        let n_map_sets = self.map_set_data.len();
         let commands = self
             .map_set_data
             .iter()
@@ -983,7 +1029,7 @@ impl Rate {
                 {
                     let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
                         label: Some(&format!("Evolution Comparison Compute Pass #{idx}")),
-                        timestamp_writes: None,
+                        timestamp_writes: scope.compute_pass(idx, n_map_sets),
                     });
                     compute_pass.set_pipeline(&self.compare_pipeline);
                     compute_pass.set_bind_group(0, &self.compare_bind_group_0, &[]);
@@ -992,10 +1038,19 @@ impl Rate {
                 }
                 encoder.finish()
             });
-        context.queue().submit(commands)
+        let submitted = context.queue().submit(commands);
+        scope.submitted(context);
+        submitted
     }
 
-    fn reduce_all_once(&self, context: Context, n: u32) -> impl SyncingFuture {
+    fn reduce_all_once(
+        &self,
+        profiler: Option<&Profiler>,
+        context: Context,
+        n: u32,
+    ) -> impl SyncingFuture {
+        let scope = ProfileScope::maybe(profiler, "Evolution Rate Reduce");
+        let n_map_sets = self.map_set_data.len();
         let commands = self
             .map_set_data
             .iter()
@@ -1010,7 +1065,7 @@ impl Rate {
                 {
                     let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
                         label: Some(&format!("Evolution Reduction Compute Pass #{idx}")),
-                        timestamp_writes: None,
+                        timestamp_writes: scope.compute_pass(idx, n_map_sets),
                     });
                     let mut reduce_buffer_pair = map_set_data
                         .reduce_buffer_pair
@@ -1023,14 +1078,16 @@ impl Rate {
                 }
                 encoder.finish()
             });
-        context.queue().submit(commands)
+        let submitted = context.queue().submit(commands);
+        scope.submitted(context);
+        submitted
     }
 
-    fn reduce_all(&self, context: Context) -> impl SyncingFuture {
+    fn reduce_all(&self, profiler: Option<&Profiler>, context: Context) -> impl SyncingFuture {
         let ns =
             itertools::iterate(IMAGE_SIZE * IMAGE_SIZE / 2, |&n| n / 2).take_while(|&n| n != 0);
 
-        ns.map(|n| self.reduce_all_once(context.borrow(), n))
+        ns.map(|n| self.reduce_all_once(profiler, context.borrow(), n))
             .last()
             .unwrap()
     }
@@ -1088,7 +1145,13 @@ impl Select {
         let context = context.into_static();
 
         async move {
-            let (scores, maps) = futures::join!(scores, maps);
+            let (scores, maps) = match futures::join!(scores, maps) {
+                (Result::Ok(scores), Result::Ok(maps)) => (scores, maps),
+                (Err(error), _) | (_, Err(error)) => {
+                    error!("failed to download the map sets to select from: {error:?}");
+                    return;
+                }
+            };
             let map_sets = maps
                 .iter()
                 .chunks(maps_per_set)
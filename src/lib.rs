@@ -1,15 +1,22 @@
-use apps::basic;
+use apps::{adapters, basic, compare, headless};
 use clap::{Parser, Subcommand};
 use color_eyre::eyre::Result;
 
 pub mod app;
 pub mod apps;
+pub mod backend;
 pub mod buffer;
+pub mod camera;
+pub mod controls;
 pub mod image;
 pub mod map;
+pub mod palette;
+pub mod profiler;
 pub mod render;
 pub mod sim;
+pub mod state;
 pub mod util;
+pub mod zoom;
 
 #[derive(Debug, Clone, Parser)]
 pub struct Cli {
@@ -20,11 +27,18 @@ pub struct Cli {
 #[derive(Debug, Clone, Subcommand)]
 pub enum AppCli {
     #[command(name = "basic")]
-    Basic(basic::Cli),
+    Basic(Box<basic::Cli>),
+    #[command(name = "compare")]
+    Compare(compare::Cli),
+    #[command(name = "headless")]
+    Headless(headless::Cli),
+    #[command(name = "adapters")]
+    Adapters(adapters::Cli),
 }
 
 impl Cli {
     pub fn run(self) -> Result<()> {
+        env_logger::init();
         self.app.run()
     }
 }
@@ -33,6 +47,9 @@ impl AppCli {
     pub fn run(self) -> Result<()> {
         match self {
             Self::Basic(basic) => basic.run(),
+            Self::Compare(compare) => compare.run(),
+            Self::Headless(headless) => headless.run(),
+            Self::Adapters(adapters) => adapters.run(),
         }
     }
 }
@@ -1,6 +1,7 @@
 use std::f32;
 
-use glam::{vec2, Affine2, Mat2, Vec2};
+use clap::ValueEnum;
+use glam::{dvec2, vec2, Affine2, DAffine2, DVec2, Mat2, Vec2};
 
 use crate::util::{mat2, Affine2Ext};
 
@@ -19,6 +20,88 @@ impl From<Affine2> for Map {
     }
 }
 
+/// A [`Map`] in double precision.
+#[derive(Debug, Clone, Copy)]
+pub struct DMap {
+    pub map: DAffine2,
+    pub probability_weight: f32,
+}
+
+impl From<Map> for DMap {
+    fn from(map: Map) -> Self {
+        Self {
+            map: map.map.as_daffine2(),
+            probability_weight: map.probability_weight,
+        }
+    }
+}
+
+impl From<DMap> for Map {
+    fn from(map: DMap) -> Self {
+        Self {
+            map: map.map.as_affine2(),
+            probability_weight: map.probability_weight,
+        }
+    }
+}
+
+/// A [`Map`] as a rotation of a shear of a scaling, followed by a translation, e.g. to edit it.
+///
+/// Reflections have a negative `scale.y`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapDecomposition {
+    pub angle: f32,
+    /// Of x by y.
+    pub shear: f32,
+    pub scale: Vec2,
+    pub translation: Vec2,
+    pub probability_weight: f32,
+    /// The linear part of a map whose y axis is a nonzero multiple of its x axis, which has no
+    /// such decomposition and is kept as is by [`MapDecomposition::compose`].
+    degenerate: Option<Mat2>,
+}
+
+impl MapDecomposition {
+    pub fn compose(&self) -> Map {
+        let matrix2 = self.degenerate.unwrap_or_else(|| {
+            let shear = Mat2::from_cols(Vec2::X, vec2(self.shear, 1.0));
+            Mat2::from_angle(self.angle) * shear * Mat2::from_diagonal(self.scale)
+        });
+        Map {
+            map: Affine2::from_mat2_translation(matrix2, self.translation),
+            probability_weight: self.probability_weight,
+        }
+    }
+
+    /// Whether `angle`, `shear` and `scale` don't describe the map and are ignored by
+    /// [`MapDecomposition::compose`].
+    pub fn is_degenerate(&self) -> bool {
+        self.degenerate.is_some()
+    }
+}
+
+impl From<Map> for MapDecomposition {
+    fn from(map: Map) -> Self {
+        let x_axis = map.map.matrix2.x_axis;
+        let angle = x_axis.y.atan2(x_axis.x);
+        // upper triangular once the rotation is undone
+        let y_axis = Mat2::from_angle(-angle) * map.map.matrix2.y_axis;
+        let degenerate = y_axis.y == 0.0 && y_axis.x != 0.0;
+        Self {
+            angle,
+            shear: if y_axis.y == 0.0 {
+                0.0
+            } else {
+                y_axis.x / y_axis.y
+            },
+            scale: vec2(x_axis.length(), y_axis.y),
+            translation: map.map.translation,
+            probability_weight: map.probability_weight,
+            degenerate: degenerate.then_some(map.map.matrix2),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Rect {
     pub min: Vec2,
@@ -31,6 +114,47 @@ impl Rect {
         let scale = self.max - midpoint;
         Affine2::from_scale_angle_translation(scale, 0.0, midpoint)
     }
+
+    /// Like [`Rect::to_clip_transform`], for a target of `width` by `height` pixels, keeping
+    /// pixels square unless `mode` is [`AspectMode::Stretch`].
+    pub fn to_clip_transform_for(&self, width: u32, height: u32, mode: AspectMode) -> Affine2 {
+        let aspect = width as f32 / height.max(1) as f32;
+        let midpoint = 0.5 * (self.min + self.max);
+        let half_size = self.max - midpoint;
+        let scale = match mode {
+            AspectMode::Letterbox => half_size.y.max(half_size.x / aspect),
+            AspectMode::Crop => half_size.y.min(half_size.x / aspect),
+            AspectMode::Stretch => return self.to_clip_transform(),
+        };
+        Affine2::from_scale_angle_translation(vec2(aspect * scale, scale), 0.0, midpoint)
+    }
+}
+
+/// How a region is fitted into a target whose aspect ratio differs from its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum AspectMode {
+    /// Show the whole region, leaving margins along the longer side of the target.
+    #[default]
+    Letterbox,
+    /// Fill the target, cropping the region along its longer side.
+    Crop,
+    /// Fill the target with the whole region, distorting it.
+    Stretch,
+}
+
+impl AspectMode {
+    /// Scale from the clip space of a target with the given width to height ratio to a space in
+    /// which its pixels are square, and which fits or covers `[-1, 1]²` following `self`.
+    pub fn clip_scale(self, aspect: f64) -> DVec2 {
+        let wide = aspect >= 1.0;
+        match self {
+            Self::Letterbox if wide => dvec2(aspect, 1.0),
+            Self::Letterbox => dvec2(1.0, aspect.recip()),
+            Self::Crop if wide => dvec2(1.0, aspect.recip()),
+            Self::Crop => dvec2(aspect, 1.0),
+            Self::Stretch => DVec2::ONE,
+        }
+    }
 }
 
 pub trait Maps {
@@ -307,3 +431,91 @@ impl Maps for Pentagon {
             .collect()
     }
 }
+
+/// The built-in map sets, e.g. to pick from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    Sierpinski,
+    Yang,
+    Barnsley,
+    SillySquare,
+    Patrick,
+    Disc,
+    Pipistrello,
+    Tunnel,
+    Weed,
+    FleurAstrale,
+    Pentagon,
+}
+
+impl Preset {
+    fn maps_impl(self) -> &'static dyn Maps {
+        match self {
+            Self::Sierpinski => &Sierpinski,
+            Self::Yang => &Yang,
+            Self::Barnsley => &Barnsley,
+            Self::SillySquare => &SillySquare,
+            Self::Patrick => &Patrick,
+            Self::Disc => &Disc,
+            Self::Pipistrello => &Pipistrello,
+            Self::Tunnel => &Tunnel,
+            Self::Weed => &Weed,
+            Self::FleurAstrale => &FleurAstrale,
+            Self::Pentagon => &Pentagon,
+        }
+    }
+}
+
+impl Maps for Preset {
+    fn region(&self) -> Rect {
+        self.maps_impl().region()
+    }
+
+    fn maps(&self) -> Vec<Map> {
+        self.maps_impl().maps()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_round_trip(matrix2: Mat2) {
+        let map = Map {
+            map: Affine2::from_mat2_translation(matrix2, vec2(0.25, -0.5)),
+            probability_weight: 2.0,
+        };
+        let composed = MapDecomposition::from(map).compose();
+        assert!(
+            composed.map.abs_diff_eq(map.map, 1e-6),
+            "{:?} became {:?}",
+            map.map,
+            composed.map
+        );
+        assert_eq!(composed.probability_weight, map.probability_weight);
+    }
+
+    #[test]
+    fn round_trip() {
+        for map in Sierpinski.maps().into_iter().chain(Yang.maps()) {
+            assert_round_trip(map.map.matrix2);
+        }
+        // sheared reflection
+        assert_round_trip(Mat2::from_cols(vec2(0.5, 0.2), vec2(0.3, -0.4)));
+        assert_round_trip(Mat2::ZERO);
+        assert_round_trip(Mat2::from_cols(Vec2::ZERO, vec2(0.3, 0.4)));
+    }
+
+    #[test]
+    fn round_trip_degenerate() {
+        for matrix2 in [
+            Mat2::from_cols(vec2(0.5, 0.0), vec2(0.3, 0.0)),
+            Mat2::from_cols(vec2(0.3, 0.4), vec2(-0.6, -0.8)),
+            Mat2::from_cols(Vec2::ZERO, vec2(0.3, 0.0)),
+        ] {
+            let map = Map::from(Affine2::from_mat2(matrix2));
+            assert!(MapDecomposition::from(map).is_degenerate());
+            assert_round_trip(matrix2);
+        }
+    }
+}
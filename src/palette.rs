@@ -0,0 +1,250 @@
+use std::{
+    convert::Infallible,
+    fs, io,
+    num::{ParseFloatError, ParseIntError},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use clap::ValueEnum;
+use image::{Rgba, RgbaImage};
+use thiserror::Error;
+
+/// A gradient of sRGB colours over `[0, 1]`, linearly interpolated between stops.
+///
+/// Only the density of the points is coloured with it so far: colouring each point by the maps
+/// that moved it needs a colour value per point, which the simulation doesn't keep yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    // sorted by position
+    stops: Vec<(f32, [f32; 4])>,
+}
+
+/// Perceptually uniform gradients from matplotlib, each approximated by 10 evenly spaced stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum BuiltinPalette {
+    #[default]
+    Viridis,
+    Magma,
+    Inferno,
+}
+
+impl BuiltinPalette {
+    pub fn palette(self) -> Palette {
+        let colors: [u32; 10] = match self {
+            Self::Viridis => [
+                0x440154, 0x482878, 0x3e4989, 0x31688e, 0x26828e, 0x1f9e89, 0x35b779, 0x6ece58,
+                0xb5de2b, 0xfde725,
+            ],
+            Self::Magma => [
+                0x000004, 0x180f3e, 0x451077, 0x721f81, 0x9f2f7f, 0xcd4071, 0xf1605d, 0xfd9567,
+                0xfec98d, 0xfcfdbf,
+            ],
+            Self::Inferno => [
+                0x000004, 0x1b0c42, 0x4b0c6b, 0x781c6d, 0xa52c60, 0xcf4446, 0xed6925, 0xfb9a06,
+                0xf7d03c, 0xfcffa4,
+            ],
+        };
+        Palette::evenly_spaced(colors.map(|rgb| {
+            let channel = |shift: u32| ((rgb >> shift) & 0xff) as f32 / 255.0;
+            [channel(16), channel(8), channel(0), 1.0]
+        }))
+    }
+
+    /// The next palette, wrapping around, e.g. to cycle through them.
+    pub fn next(self) -> Self {
+        let variants = Self::value_variants();
+        let idx = variants
+            .iter()
+            .position(|&variant| variant == self)
+            .unwrap();
+        variants[(idx + 1) % variants.len()]
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LoadPaletteError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("no colour stops")]
+    Empty,
+}
+
+impl Palette {
+    /// Panics if `stops` is empty.
+    pub fn new(mut stops: Vec<(f32, [f32; 4])>) -> Self {
+        assert!(!stops.is_empty(), "palette must have at least one stop");
+        stops.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        Self { stops }
+    }
+
+    pub fn evenly_spaced(colors: impl IntoIterator<Item = [f32; 4]>) -> Self {
+        let colors: Vec<_> = colors.into_iter().collect();
+        let last = colors.len().saturating_sub(1).max(1) as f32;
+        Self::new(
+            colors
+                .into_iter()
+                .enumerate()
+                .map(|(idx, color)| (idx as f32 / last, color))
+                .collect(),
+        )
+    }
+
+    /// Loads a flame `.map` file, or CSV stops otherwise.
+    ///
+    /// See [`Palette::parse_map`] and [`Palette::parse_csv`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadPaletteError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|extension| extension == "map") {
+            Self::parse_map(&text)
+        } else {
+            Self::parse_csv(&text)
+        }
+    }
+
+    /// Parses the flame `.map` format: one `r g b` line per evenly spaced stop, with components
+    /// in `0..=255` and anything after them ignored.
+    pub fn parse_map(text: &str) -> Result<Self, LoadPaletteError> {
+        let mut colors = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let syntax = |message: String| LoadPaletteError::Syntax {
+                line: idx + 1,
+                message,
+            };
+
+            let mut components = line.split_whitespace().map(|component| {
+                component
+                    .parse::<u8>()
+                    .map(|value| f32::from(value) / 255.0)
+                    .map_err(|error: ParseIntError| syntax(format!("{error}: `{component}`")))
+            });
+            let mut next = || {
+                components
+                    .next()
+                    .unwrap_or_else(|| Err(syntax("expected 3 components".to_owned())))
+            };
+            colors.push([next()?, next()?, next()?, 1.0]);
+        }
+
+        if colors.is_empty() {
+            return Err(LoadPaletteError::Empty);
+        }
+        Ok(Self::evenly_spaced(colors))
+    }
+
+    /// Parses `position,r,g,b[,a]` lines, with every value in `[0, 1]`.
+    pub fn parse_csv(text: &str) -> Result<Self, LoadPaletteError> {
+        let mut stops = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let syntax = |message: String| LoadPaletteError::Syntax {
+                line: idx + 1,
+                message,
+            };
+
+            let values = line
+                .split(',')
+                .map(|value| {
+                    let value = value.trim();
+                    value
+                        .parse::<f32>()
+                        .map_err(|error: ParseFloatError| syntax(format!("{error}: `{value}`")))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let stop = match values[..] {
+                [position, r, g, b] => (position, [r, g, b, 1.0]),
+                [position, r, g, b, a] => (position, [r, g, b, a]),
+                _ => {
+                    return Err(syntax(format!(
+                        "expected 4 or 5 values, got {}",
+                        values.len()
+                    )))
+                }
+            };
+            stops.push(stop);
+        }
+
+        if stops.is_empty() {
+            return Err(LoadPaletteError::Empty);
+        }
+        Ok(Self::new(stops))
+    }
+
+    pub fn sample(&self, t: f32) -> [f32; 4] {
+        let t = t.clamp(0.0, 1.0);
+        let after = self.stops.partition_point(|&(position, _)| position < t);
+        if after == 0 {
+            return self.stops[0].1;
+        }
+        if after == self.stops.len() {
+            return self.stops[after - 1].1;
+        }
+
+        let (start, from) = self.stops[after - 1];
+        let (end, to) = self.stops[after];
+        let s = if end > start {
+            (t - start) / (end - start)
+        } else {
+            0.0
+        };
+        [0, 1, 2, 3].map(|idx| from[idx] + (to[idx] - from[idx]) * s)
+    }
+
+    /// Samples `width` evenly spaced sRGB colours, e.g. for a lookup texture.
+    pub fn to_rgba8(&self, width: u32) -> Vec<[u8; 4]> {
+        let last = width.saturating_sub(1).max(1) as f32;
+        (0..width)
+            .map(|x| {
+                self.sample(x as f32 / last)
+                    .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+            })
+            .collect()
+    }
+
+    /// A horizontal strip showing the palette from left to right, for previews.
+    pub fn swatch(&self, width: u32, height: u32) -> RgbaImage {
+        let colors = self.to_rgba8(width);
+        RgbaImage::from_fn(width, height, |x, _| Rgba(colors[x as usize]))
+    }
+}
+
+impl From<BuiltinPalette> for Palette {
+    fn from(builtin: BuiltinPalette) -> Self {
+        builtin.palette()
+    }
+}
+
+/// A built-in palette name, or the path of a palette file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteSource {
+    Builtin(BuiltinPalette),
+    File(PathBuf),
+}
+
+impl PaletteSource {
+    pub fn load(&self) -> Result<Palette, LoadPaletteError> {
+        match self {
+            Self::Builtin(builtin) => Ok(builtin.palette()),
+            Self::File(path) => Palette::load(path),
+        }
+    }
+}
+
+impl FromStr for PaletteSource {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(BuiltinPalette::from_str(s, true)
+            .map_or_else(|_| Self::File(PathBuf::from(s)), Self::Builtin))
+    }
+}
@@ -0,0 +1,467 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::{self, Display, Write},
+    future::{self, Future},
+    iter, mem,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::WrapErr;
+use futures::FutureExt;
+use log::{error, warn};
+use wgpu::{
+    BufferDescriptor, BufferUsages, CommandEncoderDescriptor, ComputePassTimestampWrites, Features,
+    MapMode, QuerySet, QuerySetDescriptor, QueryType, RenderPassTimestampWrites, QUERY_SIZE,
+};
+
+use crate::{app::Context, buffer::Buffer};
+
+/// Times passes on the GPU with timestamp queries, or with the wall-clock time between submission
+/// and completion when [`Features::TIMESTAMP_QUERY`] isn't supported.
+///
+/// Timestamps are only read back by [`Profiler::resolve`], which should be called regularly.
+#[derive(Debug)]
+pub struct Profiler {
+    timestamps: Option<Timestamps>,
+    times: Arc<Mutex<BTreeMap<String, PassTimes>>>,
+    window: usize,
+}
+
+#[derive(Debug)]
+struct Timestamps {
+    query_set: QuerySet,
+    resolve_buffer: Buffer<u64>,
+    period: f32,
+    slots: Arc<Mutex<Slots>>,
+}
+
+/// Pairs of queries, each owned by one scope from [`Profiler::scope`] until its times were read
+/// back.
+#[derive(Debug)]
+struct Slots {
+    free: Vec<u32>,
+    // with the label of their scope, in the order they were submitted
+    submitted: Vec<(u32, String)>,
+    warned: bool,
+}
+
+#[derive(Debug, Default)]
+struct PassTimes {
+    recent: VecDeque<Duration>,
+    count: usize,
+}
+
+impl Profiler {
+    pub const MAX_SCOPES: u32 = 256;
+    const DEFAULT_WINDOW: usize = 128;
+
+    pub fn new(context: Context) -> Self {
+        let timestamps = context
+            .device()
+            .features()
+            .contains(Features::TIMESTAMP_QUERY)
+            .then(|| Timestamps {
+                query_set: context.device().create_query_set(&QuerySetDescriptor {
+                    label: Some("Profiler Timestamp Query Set"),
+                    ty: QueryType::Timestamp,
+                    count: 2 * Self::MAX_SCOPES,
+                }),
+                resolve_buffer: Buffer::new(
+                    2 * Self::MAX_SCOPES as usize,
+                    Some("Profiler Timestamp Resolve Buffer"),
+                    BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+                    context.borrow(),
+                ),
+                period: context.queue().get_timestamp_period(),
+                slots: Arc::new(Mutex::new(Slots {
+                    free: (0..Self::MAX_SCOPES).rev().collect(),
+                    submitted: Vec::new(),
+                    warned: false,
+                })),
+            });
+
+        Self {
+            timestamps,
+            times: Arc::default(),
+            window: Self::DEFAULT_WINDOW,
+        }
+    }
+
+    /// Number of most recent samples that statistics are computed over.
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    /// Whether passes are timed on the GPU rather than on the CPU.
+    pub fn uses_timestamps(&self) -> bool {
+        self.timestamps.is_some()
+    }
+
+    /// Starts timing the passes labelled `label`, up to [`ProfileScope::submitted`].
+    ///
+    /// With timestamp queries, at most [`Profiler::MAX_SCOPES`] scopes can be in use between
+    /// resolves, and further ones aren't timed.
+    pub fn scope(&self, label: impl Into<String>) -> ProfileScope<'_> {
+        let label = label.into();
+        let kind = match &self.timestamps {
+            Some(timestamps) => {
+                let mut slots = timestamps.slots.lock().expect("failed to lock mutex");
+                match slots.free.pop() {
+                    Some(slot) => ScopeKind::Timestamps {
+                        query_set: &timestamps.query_set,
+                        slots: &timestamps.slots,
+                        slot,
+                        label,
+                    },
+                    None => {
+                        if !mem::replace(&mut slots.warned, true) {
+                            warn!(
+                                "all {} profiler scopes are in use, not timing {label:?} and \
+                                 later passes until the next resolve",
+                                Self::MAX_SCOPES
+                            );
+                        }
+                        ScopeKind::Disabled
+                    }
+                }
+            }
+            None => ScopeKind::WallClock {
+                times: self.times.clone(),
+                window: self.window,
+                label,
+                start: Instant::now(),
+            },
+        };
+        ProfileScope { kind }
+    }
+
+    /// Reads back the timestamps of the scopes submitted since the last call and adds them to the
+    /// statistics.
+    ///
+    /// May be called from another thread than the one submitting the scopes.
+    pub fn resolve(&self, context: Context) -> impl Future<Output = ()> + 'static {
+        let Some(timestamps) = &self.timestamps else {
+            return future::ready(()).left_future();
+        };
+
+        // only scopes whose passes were submitted already, so this resolve is queued after them
+        let submitted = {
+            let mut slots = timestamps.slots.lock().expect("failed to lock mutex");
+            slots.warned = false;
+            mem::take(&mut slots.submitted)
+        };
+        if submitted.is_empty() {
+            return future::ready(()).left_future();
+        }
+
+        // copied within the same submission, so that concurrent resolves can't overwrite the
+        // resolve buffer before it is read
+        let read_buffer = context.device().create_buffer(&BufferDescriptor {
+            label: Some("Profiler Timestamp Read Buffer"),
+            size: timestamps.resolve_buffer.size(),
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = context
+            .device()
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Profiler Timestamp Resolve Command Encoder"),
+            });
+        // only queries that were written may be resolved, so each run of consecutive slots is
+        // resolved on its own, to the start of the resolve buffer as its offset must be aligned
+        let mut written: Vec<u32> = submitted.iter().map(|&(slot, _)| slot).collect();
+        written.sort_unstable();
+        for run in written.chunk_by(|a, b| a + 1 == *b) {
+            let (first, len) = (run[0], run.len() as u32);
+            encoder.resolve_query_set(
+                &timestamps.query_set,
+                2 * first..2 * (first + len),
+                &timestamps.resolve_buffer,
+                0,
+            );
+            encoder.copy_buffer_to_buffer(
+                &timestamps.resolve_buffer,
+                0,
+                &read_buffer,
+                2 * u64::from(first) * u64::from(QUERY_SIZE),
+                2 * u64::from(len) * u64::from(QUERY_SIZE),
+            );
+        }
+        let resolved = context.queue().submit(iter::once(encoder.finish()));
+
+        let slots = timestamps.slots.clone();
+        let period = timestamps.period;
+        let times = self.times.clone();
+        let window = self.window;
+
+        async move {
+            resolved.await;
+            let slice = read_buffer.slice(..);
+            let mapped = slice
+                .map_async(MapMode::Read)
+                .await
+                .wrap_err("failed to map buffer for reading");
+            let ticks = mapped.map(|()| {
+                let ticks = bytemuck::cast_slice::<_, u64>(&slice.get_mapped_range()).to_vec();
+                read_buffer.unmap();
+                ticks
+            });
+
+            let mut slots = slots.lock().expect("failed to lock mutex");
+            slots.free.extend(submitted.iter().map(|&(slot, _)| slot));
+            drop(slots);
+
+            let ticks = match ticks {
+                Ok(ticks) => ticks,
+                Err(error) => {
+                    error!("failed to download pass times: {error:?}");
+                    return;
+                }
+            };
+            let mut times = times.lock().expect("failed to lock mutex");
+            for (slot, label) in submitted {
+                let begin = 2 * slot as usize;
+                let nanos =
+                    ticks[begin + 1].saturating_sub(ticks[begin]) as f64 * f64::from(period);
+                times
+                    .entry(label)
+                    .or_default()
+                    .push(Duration::from_nanos(nanos as u64), window);
+            }
+        }
+        .right_future()
+    }
+
+    /// Records a duration measured by other means, e.g. for work done on the CPU.
+    pub fn record(&self, label: impl Into<String>, duration: Duration) {
+        self.times
+            .lock()
+            .expect("failed to lock mutex")
+            .entry(label.into())
+            .or_default()
+            .push(duration, self.window);
+    }
+
+    pub fn report(&self) -> ProfileReport {
+        let times = self.times.lock().expect("failed to lock mutex");
+        let passes = times
+            .iter()
+            .map(|(label, times)| PassReport {
+                label: label.clone(),
+                count: times.count,
+                mean: times.recent.iter().sum::<Duration>() / times.recent.len() as u32,
+                min: times.recent.iter().min().copied().unwrap_or_default(),
+                max: times.recent.iter().max().copied().unwrap_or_default(),
+            })
+            .collect();
+        ProfileReport {
+            timestamps: self.uses_timestamps(),
+            passes,
+        }
+    }
+}
+
+impl PassTimes {
+    fn push(&mut self, duration: Duration, window: usize) {
+        if self.recent.len() >= window {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(duration);
+        self.count += 1;
+    }
+}
+
+/// Timing of the passes between [`Profiler::scope`] and [`ProfileScope::submitted`].
+///
+/// With timestamp queries, the scope spans from the beginning of its first pass to the end of its
+/// last pass, so passes in between aren't timed individually.
+#[derive(Debug)]
+pub struct ProfileScope<'a> {
+    kind: ScopeKind<'a>,
+}
+
+#[derive(Debug)]
+enum ScopeKind<'a> {
+    Disabled,
+    Timestamps {
+        query_set: &'a QuerySet,
+        slots: &'a Mutex<Slots>,
+        slot: u32,
+        label: String,
+    },
+    WallClock {
+        times: Arc<Mutex<BTreeMap<String, PassTimes>>>,
+        window: usize,
+        label: String,
+        start: Instant,
+    },
+}
+
+impl ProfileScope<'_> {
+    /// A scope that doesn't time anything, for when there is no [`Profiler`].
+    pub fn disabled() -> Self {
+        Self {
+            kind: ScopeKind::Disabled,
+        }
+    }
+
+    /// Starts a scope if there is a profiler.
+    pub fn maybe<'a>(profiler: Option<&'a Profiler>, label: impl Into<String>) -> ProfileScope<'a> {
+        profiler.map_or_else(ProfileScope::disabled, |profiler| profiler.scope(label))
+    }
+
+    fn write_indices(
+        &self,
+        idx: usize,
+        len: usize,
+    ) -> Option<(&QuerySet, Option<u32>, Option<u32>)> {
+        let ScopeKind::Timestamps {
+            query_set, slot, ..
+        } = self.kind
+        else {
+            return None;
+        };
+        let begin = 2 * slot;
+        let first = (idx == 0).then_some(begin);
+        let last = (idx + 1 == len).then_some(begin + 1);
+        (first.is_some() || last.is_some()).then_some((query_set, first, last))
+    }
+
+    /// Timestamp writes for the `idx`th of the `len` compute passes of this scope.
+    pub fn compute_pass(&self, idx: usize, len: usize) -> Option<ComputePassTimestampWrites<'_>> {
+        self.write_indices(idx, len)
+            .map(|(query_set, first, last)| ComputePassTimestampWrites {
+                query_set,
+                beginning_of_pass_write_index: first,
+                end_of_pass_write_index: last,
+            })
+    }
+
+    /// Timestamp writes for the `idx`th of the `len` render passes of this scope.
+    pub fn render_pass(&self, idx: usize, len: usize) -> Option<RenderPassTimestampWrites<'_>> {
+        self.write_indices(idx, len)
+            .map(|(query_set, first, last)| RenderPassTimestampWrites {
+                query_set,
+                beginning_of_pass_write_index: first,
+                end_of_pass_write_index: last,
+            })
+    }
+
+    /// Ends the scope, to be called right after its commands are submitted.
+    pub fn submitted(mut self, context: Context) {
+        match mem::replace(&mut self.kind, ScopeKind::Disabled) {
+            ScopeKind::Disabled => {}
+            ScopeKind::Timestamps {
+                slots, slot, label, ..
+            } => slots
+                .lock()
+                .expect("failed to lock mutex")
+                .submitted
+                .push((slot, label)),
+            ScopeKind::WallClock {
+                times,
+                window,
+                label,
+                start,
+            } => context.queue().on_submitted_work_done(move || {
+                times
+                    .lock()
+                    .expect("failed to lock mutex")
+                    .entry(label)
+                    .or_default()
+                    .push(start.elapsed(), window);
+            }),
+        }
+    }
+}
+
+impl Drop for ProfileScope<'_> {
+    // scopes whose commands were never submitted give back their queries
+    fn drop(&mut self) {
+        if let ScopeKind::Timestamps { slots, slot, .. } = self.kind {
+            slots.lock().expect("failed to lock mutex").free.push(slot);
+        }
+    }
+}
+
+/// Rolling statistics of every pass timed by a [`Profiler`].
+#[derive(Debug, Clone)]
+pub struct ProfileReport {
+    /// Whether times were measured on the GPU.
+    pub timestamps: bool,
+    pub passes: Vec<PassReport>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PassReport {
+    pub label: String,
+    /// Number of times the pass was timed since the profiler was created.
+    pub count: usize,
+    pub mean: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl ProfileReport {
+    pub fn to_json(&self) -> String {
+        let mut json = format!("{{\"timestamps\":{},\"passes\":[", self.timestamps);
+        for (idx, pass) in self.passes.iter().enumerate() {
+            if idx != 0 {
+                json.push(',');
+            }
+            write!(
+                json,
+                "{{\"label\":{},\"count\":{},\"mean_ms\":{},\"min_ms\":{},\"max_ms\":{}}}",
+                json_string(&pass.label),
+                pass.count,
+                millis(pass.mean),
+                millis(pass.min),
+                millis(pass.max),
+            )
+            .expect("failed to write to string");
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+impl Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let clock = if self.timestamps { "GPU" } else { "wall-clock" };
+        write!(f, "{clock} pass times:")?;
+        for pass in &self.passes {
+            write!(
+                f,
+                "\n  {}: mean {:.3} ms, min {:.3} ms, max {:.3} ms ({} samples)",
+                pass.label,
+                millis(pass.mean),
+                millis(pass.min),
+                millis(pass.max),
+                pass.count,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => {
+                write!(json, "\\u{:04x}", c as u32).expect("failed to write to string")
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
@@ -1,22 +1,69 @@
-use std::{borrow::Cow, iter, mem, sync::OnceLock};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    future::Future,
+    iter, mem,
+    sync::{Arc, Mutex, OnceLock},
+};
 
+use color_eyre::eyre::{Result, WrapErr};
 use glam::{Affine2, Mat3};
+use image::RgbaImage;
+use itertools::Itertools;
+use log::warn;
 use wgpu::{
     include_wgsl, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
-    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendState, BufferAddress,
-    BufferBindingType, BufferUsages, Color, ColorTargetState, ColorWrites,
-    CommandEncoderDescriptor, FragmentState, LoadOp, Operations, PipelineLayoutDescriptor,
-    PrimitiveState, PrimitiveTopology, RenderPassDescriptor, RenderPipeline,
-    RenderPipelineDescriptor, ShaderStages, StoreOp, SurfaceTexture, Texture, TextureFormat,
-    TextureView, TextureViewDescriptor, VertexBufferLayout, VertexState,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BufferAddress, BufferBindingType,
+    BufferUsages, ColorTargetState, ColorWrites, CommandBuffer, CommandEncoderDescriptor, Extent3d,
+    FragmentState, LoadOp, MultisampleState, Operations, Origin3d, PipelineLayout,
+    PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, RenderPass, RenderPassDescriptor,
+    RenderPassTimestampWrites, RenderPipeline, RenderPipelineDescriptor, ShaderModule,
+    ShaderStages, StoreOp, SurfaceTexture, TexelCopyBufferInfo, TexelCopyBufferLayout,
+    TexelCopyTextureInfo, Texture, TextureAspect, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureFormatFeatureFlags, TextureUsages, TextureView, TextureViewDescriptor,
+    VertexAttribute, VertexBufferLayout, VertexState, VertexStepMode, COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+use crate::{
+    app::Context,
+    buffer::Buffer,
+    palette::Palette,
+    profiler::{ProfileScope, Profiler},
+    sim::Point,
+    util::{SyncingFuture, WgpuMat3x3},
 };
 
-use crate::{app::Context, buffer::Buffer, sim::Point, util::WgpuMat3x3};
+mod density;
+mod overlay;
+mod settings;
+mod sprite;
+mod trails;
+mod viewport;
+
+use density::DensityPass;
+pub use density::DENSITY_FEATURES;
+pub use overlay::MapOverlay;
+use overlay::Overlay;
+use settings::RenderSettingsUniform;
+pub use settings::{BlendMode, HexColor, ParseColorError, PointStyle, RenderSettings};
+use sprite::Sprites;
+use trails::{TrailPass, TRAIL_FORMAT};
+pub use viewport::Viewport;
+use viewport::ViewportClip;
 
 pub trait RenderTarget: Send + 'static {
     fn texture_view(&self) -> Cow<TextureView>;
+
+    /// `None` if unknown, e.g. for a bare [`TextureView`], which then only supports drawing
+    /// single-sampled pixel points over all of it. See [`SizedTextureView`].
+    fn size(&self) -> Option<Extent3d> {
+        None
+    }
 }
 
+const UNKNOWN_SIZE: &str = "only single-sampled pixel points can be rendered over all of a \
+                            target of unknown size, see `SizedTextureView`";
+
 impl RenderTarget for SurfaceTexture {
     fn texture_view(&self) -> Cow<TextureView> {
         Cow::Owned(self.texture.create_view(&TextureViewDescriptor {
@@ -24,6 +71,10 @@ impl RenderTarget for SurfaceTexture {
             ..Default::default()
         }))
     }
+
+    fn size(&self) -> Option<Extent3d> {
+        Some(self.texture.size())
+    }
 }
 
 impl RenderTarget for Texture {
@@ -33,79 +84,358 @@ impl RenderTarget for Texture {
             ..Default::default()
         }))
     }
+
+    fn size(&self) -> Option<Extent3d> {
+        Some(Texture::size(self))
+    }
 }
 
 impl RenderTarget for TextureView {
-    fn texture_view(&self) -> Cow<TextureView> {
+    fn texture_view(&self) -> Cow<'_, TextureView> {
         Cow::Borrowed(self)
     }
 }
 
+/// A [`TextureView`] together with the size of its texture, supporting all render settings.
 #[derive(Debug, Clone)]
+pub struct SizedTextureView {
+    pub view: TextureView,
+    pub size: Extent3d,
+}
+
+impl RenderTarget for SizedTextureView {
+    fn texture_view(&self) -> Cow<'_, TextureView> {
+        Cow::Borrowed(&self.view)
+    }
+
+    fn size(&self) -> Option<Extent3d> {
+        Some(self.size)
+    }
+}
+
+#[derive(Debug)]
 pub struct Renderer {
-    pipeline: RenderPipeline,
+    // created as the settings require them
+    pipelines: HashMap<PipelineKey, RenderPipeline>,
+    srgb: bool,
+    settings: RenderSettings,
+    settings_buffer: Buffer<RenderSettingsUniform>,
+    settings_bind_group_layout: BindGroupLayout,
+    settings_bind_group: BindGroup,
+    texture_format: TextureFormat,
+    pipeline_layout: PipelineLayout,
+    sprite_pipeline_layout: PipelineLayout,
+    shader: ShaderModule,
+    sprites: Sprites,
+    // by size and sample count of the target
+    multisample_target: Mutex<Option<(Extent3d, u32, TextureView)>>,
+    density: Option<DensityPass>,
+    trails: Option<TrailPass>,
+    overlay: Option<Overlay>,
+    profiler: Option<Arc<Profiler>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    // `None` when counting points for a `DensityPass`
+    blend: Option<BlendMode>,
+    sprite: bool,
+    samples: u32,
+    format: TextureFormat,
 }
 
 #[derive(Debug)]
 pub struct Camera {
-    _buffer: Buffer<WgpuMat3x3>,
+    buffer: Buffer<WgpuMat3x3>,
+    clip_buffer: Buffer<ViewportClip>,
+    // as last written to `clip_buffer`
+    clip: Mutex<ViewportClip>,
     bind_group: BindGroup,
 }
 
 impl Renderer {
     pub fn new(context: Context, texture_format: TextureFormat) -> Self {
-        dbg!(texture_format);
+        let srgb = texture_format.is_srgb();
+        let settings = RenderSettings::default();
+        let settings_buffer = Buffer::from_data(
+            &[settings.gpu_repr(srgb)],
+            Some("Render Settings Buffer"),
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            context.borrow(),
+        );
+        let settings_bind_group_layout =
+            context
+                .device()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("Render Settings Bind Group Layout"),
+                    entries: &[BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+        let settings_bind_group = context.device().create_bind_group(&BindGroupDescriptor {
+            label: Some("Render Settings Bind Group"),
+            layout: &settings_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: settings_buffer.as_entire_binding(),
+            }],
+        });
 
         let pipeline_layout = context
             .device()
             .create_pipeline_layout(&PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[Camera::bind_group_layout(context.borrow())],
+                bind_group_layouts: &[
+                    Camera::bind_group_layout(context.borrow()),
+                    &settings_bind_group_layout,
+                ],
                 push_constant_ranges: &[],
             });
 
+        let sprites = Sprites::new(settings.point_size, context.borrow());
+        let sprite_pipeline_layout =
+            context
+                .device()
+                .create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("Sprite Render Pipeline Layout"),
+                    bind_group_layouts: &[
+                        Camera::bind_group_layout(context.borrow()),
+                        &settings_bind_group_layout,
+                        sprites.bind_group_layout(),
+                    ],
+                    push_constant_ranges: &[],
+                });
+
         let shader = context
             .device()
             .create_shader_module(include_wgsl!("render.wgsl"));
 
-        let vertex_buffer_layout = VertexBufferLayout {
-            array_stride: mem::size_of::<Point>() as BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+        let mut renderer = Self {
+            pipelines: HashMap::new(),
+            srgb,
+            settings,
+            settings_buffer,
+            settings_bind_group_layout,
+            settings_bind_group,
+            texture_format,
+            pipeline_layout,
+            sprite_pipeline_layout,
+            shader,
+            sprites,
+            multisample_target: Mutex::new(None),
+            density: None,
+            trails: None,
+            overlay: None,
+            profiler: None,
+        };
+        renderer.create_pipeline(context);
+        renderer
+    }
+
+    fn pipeline_key(&self) -> PipelineKey {
+        let sprite = self.settings.point_style.is_sprite();
+        match (&self.density, &self.trails) {
+            (Some(density), _) => PipelineKey {
+                blend: None,
+                sprite,
+                samples: 1,
+                format: density.format(),
+            },
+            (None, Some(_)) => PipelineKey {
+                blend: Some(self.settings.blend),
+                sprite,
+                samples: 1,
+                format: TRAIL_FORMAT,
+            },
+            (None, None) => PipelineKey {
+                blend: Some(self.settings.blend),
+                sprite,
+                samples: self.settings.samples,
+                format: self.texture_format,
+            },
+        }
+    }
+
+    // for the current settings, if it doesn't exist yet
+    fn create_pipeline(&mut self, context: Context) {
+        let key = self.pipeline_key();
+        if let Some(overlay) = &mut self.overlay {
+            overlay.create_pipeline(key.samples, self.texture_format, context.borrow());
+        }
+        if self.pipelines.contains_key(&key) {
+            return;
+        }
+
+        let (layout, vertex_entry_point, step_mode, topology) = if key.sprite {
+            (
+                &self.sprite_pipeline_layout,
+                "sprite_vertex",
+                VertexStepMode::Instance,
+                PrimitiveTopology::TriangleStrip,
+            )
+        } else {
+            (
+                &self.pipeline_layout,
+                "vertex",
+                VertexStepMode::Vertex,
+                PrimitiveTopology::PointList,
+            )
+        };
+        let (target, fragment_entry_point, description) = match key.blend {
+            Some(blend) => (
+                ColorTargetState {
+                    format: key.format,
+                    blend: Some(blend.blend_state()),
+                    write_mask: ColorWrites::ALL,
+                },
+                if key.sprite {
+                    "sprite_fragment"
+                } else {
+                    "fragment"
+                },
+                format!("{blend:?} Blending, {:?}", key.format),
+            ),
+            None => (
+                DensityPass::count_target(key.format),
+                if key.sprite { "sprite_count" } else { "count" },
+                "Density Count".to_owned(),
+            ),
         };
 
         let pipeline = context
             .device()
             .create_render_pipeline(&RenderPipelineDescriptor {
-                label: Some("Render Pipeline"),
-                layout: Some(&pipeline_layout),
+                label: Some(&format!(
+                    "{} Render Pipeline ({description}, {}×)",
+                    if key.sprite { "Sprite" } else { "Point" },
+                    key.samples,
+                )),
+                layout: Some(layout),
                 vertex: VertexState {
-                    module: &shader,
-                    buffers: &[vertex_buffer_layout],
-                    entry_point: None,
+                    module: &self.shader,
+                    buffers: &[Self::vertex_buffer_layout(step_mode)],
+                    entry_point: Some(vertex_entry_point),
                     compilation_options: Default::default(),
                 },
                 fragment: Some(FragmentState {
-                    module: &shader,
-                    targets: &[Some(ColorTargetState {
-                        format: texture_format,
-                        blend: Some(BlendState::REPLACE),
-                        write_mask: ColorWrites::ALL,
-                    })],
-                    entry_point: None,
+                    module: &self.shader,
+                    targets: &[Some(target)],
+                    entry_point: Some(fragment_entry_point),
                     compilation_options: Default::default(),
                 }),
                 primitive: PrimitiveState {
-                    topology: PrimitiveTopology::PointList,
+                    topology,
                     ..Default::default()
                 },
                 depth_stencil: None,
-                multisample: Default::default(),
+                multisample: MultisampleState {
+                    count: key.samples,
+                    ..Default::default()
+                },
                 multiview: None,
                 cache: None,
             });
+        self.pipelines.insert(key, pipeline);
+    }
+
+    fn vertex_buffer_layout(step_mode: VertexStepMode) -> VertexBufferLayout<'static> {
+        const ATTRIBUTES: [VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x2];
+        VertexBufferLayout {
+            array_stride: mem::size_of::<Point>() as BufferAddress,
+            step_mode,
+            attributes: &ATTRIBUTES,
+        }
+    }
 
-        Self { pipeline }
+    /// Renders the density of the points mapped through `palette` instead of the foreground
+    /// colour, or stops doing so if `None`.
+    pub fn set_palette(&mut self, palette: Option<&Palette>, context: Context) {
+        match (palette, &mut self.density) {
+            (None, density) => *density = None,
+            (Some(palette), Some(density)) => {
+                density.set_palette(palette, self.srgb, context.borrow())
+            }
+            (Some(palette), density @ None) => {
+                *density = Some(DensityPass::new(
+                    palette,
+                    &self.settings_bind_group_layout,
+                    self.texture_format,
+                    context.borrow(),
+                ))
+            }
+        }
+        self.create_pipeline(context);
+    }
+
+    /// Draws `overlay` over the points, or stops doing so if `None`.
+    pub fn set_overlay(&mut self, overlay: Option<&MapOverlay>, context: Context) {
+        match (overlay, &mut self.overlay) {
+            (None, current) => *current = None,
+            (Some(overlay), Some(current)) => {
+                current.set_overlay(overlay, self.srgb, context.borrow())
+            }
+            (Some(overlay), current @ None) => {
+                *current = Some(Overlay::new(overlay, self.srgb, context.borrow()))
+            }
+        }
+        self.create_pipeline(context);
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    /// Takes effect from the next render.
+    ///
+    /// Falls back to 1 sample per pixel if [`RenderSettings::samples`] isn't supported for the
+    /// texture format.
+    pub fn set_settings(&mut self, mut settings: RenderSettings, context: Context) {
+        let features = context
+            .adapter()
+            .get_texture_format_features(self.texture_format);
+        if !features.flags.sample_count_supported(settings.samples)
+            || settings.samples > 1
+                && !features
+                    .flags
+                    .contains(TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE)
+        {
+            warn!(
+                "{} samples per pixel aren't supported for {:?}, disabling multisampling",
+                settings.samples, self.texture_format
+            );
+            settings.samples = 1;
+        }
+
+        self.settings = settings;
+        self.settings_buffer
+            .write(0, &[settings.gpu_repr(self.srgb)], context.borrow());
+        self.sprites.set_point_size(settings.point_size);
+        match (settings.trails, &self.trails) {
+            (None, _) => self.trails = None,
+            (Some(_), Some(_)) => {}
+            (Some(_), None) => {
+                self.trails = Some(TrailPass::new(self.texture_format, context.borrow()))
+            }
+        }
+        self.create_pipeline(context);
+    }
+
+    /// Forgets the trails of [`RenderSettings::trails`], e.g. after the view moved.
+    pub fn clear_trails(&mut self) {
+        if let Some(trails) = &mut self.trails {
+            trails.clear();
+        }
+    }
+
+    pub fn set_profiler(&mut self, profiler: Option<Arc<Profiler>>) {
+        self.profiler = profiler;
     }
 
     pub fn render<T: RenderTarget>(
@@ -123,37 +453,336 @@ impl Renderer {
         jobs: impl Iterator<Item = (&'pts Buffer<Point>, &'cam Camera, &'tgt T)>,
         context: Context,
     ) -> wgpu_async::WgpuFuture<()> {
-        let commands = jobs.map(|(points, camera, target)| {
-            let texture_view = target.texture_view();
+        let scope = ProfileScope::maybe(self.profiler.as_deref(), "Render");
+        let jobs = jobs.collect_vec();
+        let n_passes = self.passes_per_target() * jobs.len();
 
-            let mut encoder = context
-                .device()
-                .create_command_encoder(&CommandEncoderDescriptor {
-                    label: Some("Render Command Encoder"),
-                });
-            {
+        let commands = jobs
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (points, camera, target))| {
+                // ignored for targets of unknown size
+                let viewport = Viewport::full(target.size().unwrap_or_default());
+                self.encode(
+                    &[(points, camera, viewport)],
+                    target,
+                    self.timestamp_writes(&scope, idx, n_passes),
+                    context.borrow(),
+                )
+            });
+
+        let submitted = context.queue().submit(commands);
+        scope.submitted(context);
+        submitted
+    }
+
+    /// Renders each of `panes` into its own viewport of `target`, e.g. to compare simulations side
+    /// by side. The rest of the target is cleared to the background colour.
+    ///
+    /// Each camera should only be used by one of the panes.
+    ///
+    /// Panics if the size of `target` is unknown.
+    pub fn render_viewports<'pts, 'cam, T: RenderTarget>(
+        &self,
+        panes: impl IntoIterator<Item = (&'pts Buffer<Point>, &'cam Camera, Viewport)>,
+        target: &T,
+        context: Context,
+    ) -> wgpu_async::WgpuFuture<()> {
+        assert!(target.size().is_some(), "{UNKNOWN_SIZE}");
+        let scope = ProfileScope::maybe(self.profiler.as_deref(), "Render");
+        let panes = panes.into_iter().collect_vec();
+        let command = self.encode(
+            &panes,
+            target,
+            self.timestamp_writes(&scope, 0, self.passes_per_target()),
+            context.borrow(),
+        );
+
+        let submitted = context.queue().submit(iter::once(command));
+        scope.submitted(context);
+        submitted
+    }
+
+    fn passes_per_target(&self) -> usize {
+        if self.density.is_some() || self.trails.is_some() {
+            2
+        } else {
+            1
+        }
+    }
+
+    // of the passes rendering the `idx`th target
+    fn timestamp_writes<'a>(
+        &self,
+        scope: &'a ProfileScope,
+        idx: usize,
+        n_passes: usize,
+    ) -> [Option<RenderPassTimestampWrites<'a>>; 2] {
+        if self.passes_per_target() == 2 {
+            [
+                scope.render_pass(2 * idx, n_passes),
+                scope.render_pass(2 * idx + 1, n_passes),
+            ]
+        } else {
+            [scope.render_pass(idx, n_passes), None]
+        }
+    }
+
+    fn encode<T: RenderTarget>(
+        &self,
+        panes: &[(&Buffer<Point>, &Camera, Viewport)],
+        target: &T,
+        timestamp_writes: [Option<RenderPassTimestampWrites>; 2],
+        context: Context,
+    ) -> CommandBuffer {
+        let texture_view = target.texture_view();
+        let size = target.size();
+        let known_size = || size.expect(UNKNOWN_SIZE);
+        if self.pipeline_key().sprite {
+            known_size();
+        }
+        let draw_points = |render_pass: &mut RenderPass<'_>| {
+            for (points, camera, viewport) in panes {
+                if let Some(clip) = viewport.apply(render_pass, size) {
+                    camera.set_clip(clip, context.borrow());
+                    self.draw_points(
+                        render_pass,
+                        points,
+                        camera,
+                        viewport.size(),
+                        context.borrow(),
+                    );
+                }
+            }
+        };
+        let draw_overlay = |render_pass: &mut RenderPass<'_>, samples| {
+            if let Some(overlay) = &self.overlay {
+                for (_, camera, viewport) in panes {
+                    if let Some(clip) = viewport.apply(render_pass, size) {
+                        camera.set_clip(clip, context.borrow());
+                        overlay.draw(render_pass, camera, samples);
+                    }
+                }
+            }
+        };
+
+        let mut encoder = context
+            .device()
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Render Command Encoder"),
+            });
+
+        if let Some(density) = &self.density {
+            density.encode(
+                &mut encoder,
+                draw_points,
+                &self.settings_bind_group,
+                &texture_view,
+                known_size(),
+                timestamp_writes,
+                context.borrow(),
+            );
+
+            if self.overlay.is_some() {
                 let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                    label: Some("Render Pass"),
+                    label: Some("Overlay Render Pass"),
                     color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                         view: &texture_view,
                         resolve_target: None,
                         ops: Operations {
-                            load: LoadOp::Clear(Color::BLACK),
+                            load: LoadOp::Load,
                             store: StoreOp::Store,
                         },
                     })],
                     ..Default::default()
                 });
+                draw_overlay(&mut render_pass, 1);
+            }
+            return encoder.finish();
+        }
+
+        if let (Some(trails), Some(fade)) = (&self.trails, self.settings.trails) {
+            trails.encode(
+                &mut encoder,
+                draw_points,
+                |render_pass| draw_overlay(render_pass, 1),
+                fade,
+                self.settings.clear_color(self.srgb),
+                &texture_view,
+                known_size(),
+                timestamp_writes,
+                context.borrow(),
+            );
+            return encoder.finish();
+        }
+
+        // render into the multisampled texture and resolve it into the target
+        let multisample_view = (self.settings.samples > 1)
+            .then(|| self.multisample_view(known_size(), context.borrow()));
+        let (view, resolve_target, store) = match &multisample_view {
+            Some(multisample_view) => (multisample_view, Some(&*texture_view), StoreOp::Discard),
+            None => (&*texture_view, None, StoreOp::Store),
+        };
+
+        {
+            let [timestamp_writes, _] = timestamp_writes;
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target,
+                    ops: Operations {
+                        load: LoadOp::Clear(self.settings.clear_color(self.srgb)),
+                        store,
+                    },
+                })],
+                timestamp_writes,
+                ..Default::default()
+            });
+            draw_points(&mut render_pass);
+            draw_overlay(&mut render_pass, self.settings.samples);
+        }
+        encoder.finish()
+    }
 
-                render_pass.set_pipeline(&self.pipeline);
-                render_pass.set_vertex_buffer(0, *points.slice(..));
-                render_pass.set_bind_group(0, &camera.bind_group, &[]);
-                render_pass.draw(0..points.len_u32(), 0..1);
+    fn draw_points(
+        &self,
+        render_pass: &mut RenderPass<'_>,
+        points: &Buffer<Point>,
+        camera: &Camera,
+        size: Extent3d,
+        context: Context,
+    ) {
+        let key = self.pipeline_key();
+        render_pass.set_pipeline(&self.pipelines[&key]);
+        render_pass.set_vertex_buffer(0, *points.slice(..));
+        render_pass.set_bind_group(0, &camera.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.settings_bind_group, &[]);
+        if key.sprite {
+            render_pass.set_bind_group(2, &self.sprites.bind_group(size, context), &[]);
+            render_pass.draw(0..4, 0..points.len_u32());
+        } else {
+            render_pass.draw(0..points.len_u32(), 0..1);
+        }
+    }
+
+    fn multisample_view(&self, size: Extent3d, context: Context) -> TextureView {
+        let samples = self.settings.samples;
+        let mut multisample_target = self
+            .multisample_target
+            .lock()
+            .expect("failed to lock mutex");
+        if let Some((target_size, target_samples, view)) = &*multisample_target {
+            if *target_size == size && *target_samples == samples {
+                return view.clone();
             }
-            encoder.finish()
+        }
+
+        let texture = context.device().create_texture(&TextureDescriptor {
+            label: Some("Multisample Render Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: samples,
+            dimension: TextureDimension::D2,
+            format: self.texture_format,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[self.texture_format],
+        });
+        let view = texture.create_view(&TextureViewDescriptor {
+            label: Some("Multisample Render Texture View"),
+            ..Default::default()
         });
+        *multisample_target = Some((size, samples, view.clone()));
+        view
+    }
 
-        context.queue().submit(commands)
+    /// Renders `points` into a new `width`×`height` image.
+    ///
+    /// The renderer must have been created with [`TextureFormat::Rgba8Unorm`].
+    pub fn render_to_image(
+        &self,
+        points: &Buffer<Point>,
+        camera: &Camera,
+        width: u32,
+        height: u32,
+        context: Context,
+    ) -> impl Future<Output = Result<RgbaImage>> + 'static {
+        let texture = context.device().create_texture(&TextureDescriptor {
+            label: Some("Offscreen Render Texture"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[TextureFormat::Rgba8Unorm],
+        });
+        self.render(points, camera, &texture, context.borrow())
+            .ignore();
+        download_texture(&texture, context)
+    }
+}
+
+/// Downloads an [`TextureFormat::Rgba8Unorm`] texture into an image.
+pub fn download_texture(
+    texture: &Texture,
+    context: Context,
+) -> impl Future<Output = Result<RgbaImage>> + 'static {
+    let Extent3d { width, height, .. } = texture.size();
+    let bytes_per_row = (width * 4).next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let copy_buffer = Buffer::<u8>::new(
+        (bytes_per_row * height) as usize,
+        Some("Texture Download Buffer"),
+        BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        context.borrow(),
+    );
+    let mut copy_encoder = context
+        .device()
+        .create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Texture Download Command Encoder"),
+        });
+    copy_encoder.copy_texture_to_buffer(
+        TexelCopyTextureInfo {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        TexelCopyBufferInfo {
+            buffer: &copy_buffer,
+            layout: TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        texture.size(),
+    );
+    let command = copy_encoder.finish();
+
+    let context = context.into_static();
+    async move {
+        context.queue().submit(iter::once(command)).await;
+        let slice = copy_buffer.slice(..);
+        slice
+            .map_async(wgpu::MapMode::Read)
+            .await
+            .wrap_err("failed to map buffer for reading")?;
+        let bytes = {
+            let mapped_range = slice.get_mapped_range();
+            mapped_range
+                .chunks(bytes_per_row as usize)
+                .flat_map(|row| &row[..width as usize * 4])
+                .copied()
+                .collect()
+        };
+        copy_buffer.unmap();
+        Ok(RgbaImage::from_vec(width, height, bytes).expect("failed to create image"))
     }
 }
 
@@ -161,16 +790,30 @@ impl Camera {
     const BIND_GROUP_LAYOUT_DESCRIPTOR: BindGroupLayoutDescriptor<'static> =
         BindGroupLayoutDescriptor {
             label: Some("Camera Bind Group Layout"),
-            entries: &[BindGroupLayoutEntry {
-                binding: 0,
-                visibility: ShaderStages::VERTEX,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                // inverse transform
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                // viewport clip
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
         };
 
     fn bind_group_layout(context: Context) -> &'static BindGroupLayout {
@@ -182,30 +825,61 @@ impl Camera {
         })
     }
 
+    /// `transform` maps clip space to the coordinates of the points.
     pub fn new(transform: Affine2, context: Context) -> Self {
-        let mat = [WgpuMat3x3::from(Mat3::from(transform.inverse()))];
-        let bytes = bytemuck::cast_slice(&mat);
         let buffer = Buffer::from_data(
-            bytes,
+            &[Self::gpu_repr(transform)],
             Some("Camera Buffer"),
-            BufferUsages::UNIFORM,
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            context.borrow(),
+        );
+        let clip_buffer = Buffer::from_data(
+            &[ViewportClip::IDENTITY],
+            Some("Camera Viewport Clip Buffer"),
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
             context.borrow(),
         );
 
         let bind_group = context.device().create_bind_group(&BindGroupDescriptor {
             label: Some("Camera Bind Group"),
             layout: Self::bind_group_layout(context.borrow()),
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: clip_buffer.as_entire_binding(),
+                },
+            ],
         });
 
         Self {
-            _buffer: buffer,
+            buffer,
+            clip_buffer,
+            clip: Mutex::new(ViewportClip::IDENTITY),
             bind_group,
         }
     }
+
+    /// Moves the camera, taking effect from the next submitted render.
+    pub fn set_transform(&self, transform: Affine2, context: Context) {
+        self.buffer.write(0, &[Self::gpu_repr(transform)], context);
+    }
+
+    // a camera is drawn into one viewport per render, as the last clip written is used for all
+    fn set_clip(&self, clip: ViewportClip, context: Context) {
+        let mut current = self.clip.lock().expect("failed to lock mutex");
+        if *current != clip {
+            *current = clip;
+            self.clip_buffer.write(0, &[clip], context);
+        }
+    }
+
+    fn gpu_repr(transform: Affine2) -> WgpuMat3x3 {
+        WgpuMat3x3::from(Mat3::from(transform.inverse()))
+    }
 }
 
 impl AsRef<Camera> for Camera {
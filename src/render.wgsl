@@ -1,10 +1,80 @@
+struct RenderSettings {
+    foreground: vec4<f32>,
+    background: vec4<f32>,
+    saturation: f32,
+    point_style: u32,
+}
+
+// `PointStyle`
+const POINT_STYLE_DISC: u32 = 1u;
+
+struct Sprite {
+    // half the size of a point in clip space
+    radius: vec2<f32>,
+}
+
+// `ViewportClip`: maps clip space of the whole viewport to that of its part inside the target
+struct ViewportClip {
+    scale: vec2<f32>,
+    offset: vec2<f32>,
+}
+
 @group(0) @binding(0) var<uniform> inverse_camera: mat3x3<f32>;
+@group(0) @binding(1) var<uniform> viewport_clip: ViewportClip;
+@group(1) @binding(0) var<uniform> settings: RenderSettings;
+@group(2) @binding(0) var<uniform> sprite: Sprite;
 
 @vertex fn vertex(@location(0) point: vec2<f32>) -> @builtin(position) vec4<f32> {
     let clip_point = (inverse_camera * vec3<f32>(point, 1.0)).xy;
-    return vec4<f32>(clip_point, 0.0, 1.0);
+    return vec4<f32>(clip_point * viewport_clip.scale + viewport_clip.offset, 0.0, 1.0);
 }
 
 @fragment fn fragment(@builtin(position) point: vec4<f32>) -> @location(0) vec4<f32> {
+    return settings.foreground;
+}
+
+// counts the points hitting each pixel, with additive blending
+@fragment fn count(@builtin(position) point: vec4<f32>) -> @location(0) vec4<f32> {
     return vec4<f32>(1.0);
 }
+
+struct SpriteVertex {
+    @builtin(position) position: vec4<f32>,
+    // in [-1, 1]² over the quad
+    @location(0) offset: vec2<f32>,
+}
+
+// one instance per point, drawn as a 4-vertex triangle strip
+@vertex fn sprite_vertex(
+    @builtin(vertex_index) idx: u32,
+    @location(0) point: vec2<f32>,
+) -> SpriteVertex {
+    let corner = vec2<f32>(f32(idx & 1u), f32((idx >> 1u) & 1u)) * 2.0 - 1.0;
+    let clip_point = (inverse_camera * vec3<f32>(point, 1.0)).xy + corner * sprite.radius;
+    let position = clip_point * viewport_clip.scale + viewport_clip.offset;
+    return SpriteVertex(vec4<f32>(position, 0.0, 1.0), corner);
+}
+
+// weight of a point at `offset` from its centre
+fn footprint(offset: vec2<f32>) -> f32 {
+    let r = length(offset);
+    if settings.point_style == POINT_STYLE_DISC {
+        // anti-aliased over about a pixel
+        let edge = fwidth(r);
+        return 1.0 - smoothstep(1.0 - edge, 1.0, r);
+    }
+    // Gaussian, cut off at 3 standard deviations
+    return select(0.0, exp(-4.5 * r * r), r < 1.0);
+}
+
+@fragment fn sprite_fragment(vertex: SpriteVertex) -> @location(0) vec4<f32> {
+    let weight = footprint(vertex.offset);
+    if weight <= 0.0 {
+        discard;
+    }
+    return vec4<f32>(settings.foreground.rgb, settings.foreground.a * weight);
+}
+
+@fragment fn sprite_count(vertex: SpriteVertex) -> @location(0) vec4<f32> {
+    return vec4<f32>(footprint(vertex.offset));
+}
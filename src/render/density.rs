@@ -0,0 +1,304 @@
+use std::sync::Mutex;
+
+use log::warn;
+use wgpu::{
+    include_wgsl, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendComponent,
+    BlendFactor, BlendOperation, BlendState, Color, ColorTargetState, ColorWrites, CommandEncoder,
+    Extent3d, Features, FragmentState, LoadOp, Operations, PipelineLayoutDescriptor,
+    PrimitiveState, PrimitiveTopology, RenderPass, RenderPassColorAttachment, RenderPassDescriptor,
+    RenderPassTimestampWrites, RenderPipeline, RenderPipelineDescriptor, ShaderStages, StoreOp,
+    TexelCopyBufferLayout, TexelCopyTextureInfo, Texture, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureFormatFeatureFlags, TextureSampleType, TextureUsages,
+    TextureView, TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+use crate::{app::Context, palette::Palette};
+
+/// Device features needed to count points in 32-bit floats, see [`DensityPass::format`].
+pub const DENSITY_FEATURES: Features = Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+const PALETTE_WIDTH: u32 = 256;
+
+/// Renders points by counting them in each pixel, then mapping the counts through a [`Palette`].
+#[derive(Debug)]
+pub(super) struct DensityPass {
+    colorize_pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    palette_view: TextureView,
+    format: TextureFormat,
+    target: Mutex<Option<DensityTarget>>,
+}
+
+#[derive(Debug)]
+struct DensityTarget {
+    size: Extent3d,
+    view: TextureView,
+    bind_group: BindGroup,
+}
+
+impl DensityPass {
+    pub(super) fn new(
+        palette: &Palette,
+        settings_bind_group_layout: &BindGroupLayout,
+        texture_format: TextureFormat,
+        context: Context,
+    ) -> Self {
+        let texture_entry = |binding, view_dimension| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: false },
+                view_dimension,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout =
+            context
+                .device()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("Density Colorize Bind Group Layout"),
+                    entries: &[
+                        // density
+                        texture_entry(0, TextureViewDimension::D2),
+                        // palette
+                        texture_entry(1, TextureViewDimension::D1),
+                    ],
+                });
+
+        let colorize_pipeline_layout =
+            context
+                .device()
+                .create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("Density Colorize Pipeline Layout"),
+                    bind_group_layouts: &[&bind_group_layout, settings_bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+
+        let colorize_shader = context
+            .device()
+            .create_shader_module(include_wgsl!("density.wgsl"));
+
+        let colorize_pipeline =
+            context
+                .device()
+                .create_render_pipeline(&RenderPipelineDescriptor {
+                    label: Some("Density Colorize Render Pipeline"),
+                    layout: Some(&colorize_pipeline_layout),
+                    vertex: VertexState {
+                        module: &colorize_shader,
+                        buffers: &[],
+                        entry_point: None,
+                        compilation_options: Default::default(),
+                    },
+                    fragment: Some(FragmentState {
+                        module: &colorize_shader,
+                        targets: &[Some(ColorTargetState {
+                            format: texture_format,
+                            blend: Some(BlendState::REPLACE),
+                            write_mask: ColorWrites::ALL,
+                        })],
+                        entry_point: None,
+                        compilation_options: Default::default(),
+                    }),
+                    primitive: PrimitiveState {
+                        topology: PrimitiveTopology::TriangleList,
+                        ..Default::default()
+                    },
+                    depth_stencil: None,
+                    multisample: Default::default(),
+                    multiview: None,
+                    cache: None,
+                });
+
+        Self {
+            colorize_pipeline,
+            bind_group_layout,
+            palette_view: Self::palette_view(palette, texture_format.is_srgb(), context.borrow()),
+            format: Self::density_format(context),
+            target: Mutex::new(None),
+        }
+    }
+
+    /// Counts points in 32-bit floats where the adapter can blend them, as half floats stop
+    /// counting at 2048 points in a pixel.
+    fn density_format(context: Context) -> TextureFormat {
+        let blendable = context.device().features().contains(DENSITY_FEATURES)
+            && context
+                .adapter()
+                .get_texture_format_features(TextureFormat::R32Float)
+                .flags
+                .contains(TextureFormatFeatureFlags::BLENDABLE);
+        if blendable {
+            TextureFormat::R32Float
+        } else {
+            warn!("R32Float isn't blendable, counting points in R16Float, which saturates at 2048");
+            TextureFormat::R16Float
+        }
+    }
+
+    /// Format of the texture that points are counted into, see [`DensityPass::count_target`].
+    pub(super) fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    fn palette_view(palette: &Palette, srgb: bool, context: Context) -> TextureView {
+        // sampled colours are written as is, so only decode them if the target re-encodes them
+        let format = if srgb {
+            TextureFormat::Rgba8UnormSrgb
+        } else {
+            TextureFormat::Rgba8Unorm
+        };
+        let size = Extent3d {
+            width: PALETTE_WIDTH,
+            height: 1,
+            depth_or_array_layers: 1,
+        };
+        let texture = context.device().create_texture(&TextureDescriptor {
+            label: Some("Palette Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D1,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[format],
+        });
+        context.queue().write_texture(
+            TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: Default::default(),
+                aspect: TextureAspect::All,
+            },
+            bytemuck::cast_slice(&palette.to_rgba8(PALETTE_WIDTH)),
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(PALETTE_WIDTH * 4),
+                rows_per_image: None,
+            },
+            size,
+        );
+        texture.create_view(&TextureViewDescriptor {
+            label: Some("Palette Texture View"),
+            ..Default::default()
+        })
+    }
+
+    pub(super) fn set_palette(&mut self, palette: &Palette, srgb: bool, context: Context) {
+        self.palette_view = Self::palette_view(palette, srgb, context);
+        // the bind group refers to the previous palette
+        *self.target.get_mut().expect("failed to lock mutex") = None;
+    }
+
+    /// Colour target of the pipelines counting points into `format`, which add up their red
+    /// channel.
+    pub(super) fn count_target(format: TextureFormat) -> ColorTargetState {
+        ColorTargetState {
+            format,
+            blend: Some(BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::REPLACE,
+            }),
+            write_mask: ColorWrites::RED,
+        }
+    }
+
+    /// Encodes counting the points with `draw_points` into a density texture the size of the
+    /// target, then colouring the target.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn encode(
+        &self,
+        encoder: &mut CommandEncoder,
+        draw_points: impl FnOnce(&mut RenderPass<'_>),
+        settings_bind_group: &BindGroup,
+        target: &TextureView,
+        size: Extent3d,
+        timestamp_writes: [Option<RenderPassTimestampWrites>; 2],
+        context: Context,
+    ) {
+        let mut density_target = self.target.lock().expect("failed to lock mutex");
+        let density_target = match &mut *density_target {
+            Some(density_target) if density_target.size == size => density_target,
+            density_target => density_target.insert(self.density_target(size, context)),
+        };
+        let [count_timestamp_writes, colorize_timestamp_writes] = timestamp_writes;
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Density Count Render Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &density_target.view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::TRANSPARENT),
+                        store: StoreOp::Store,
+                    },
+                })],
+                timestamp_writes: count_timestamp_writes,
+                ..Default::default()
+            });
+            draw_points(&mut render_pass);
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Density Colorize Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: StoreOp::Store,
+                },
+            })],
+            timestamp_writes: colorize_timestamp_writes,
+            ..Default::default()
+        });
+        render_pass.set_pipeline(&self.colorize_pipeline);
+        render_pass.set_bind_group(0, &density_target.bind_group, &[]);
+        render_pass.set_bind_group(1, settings_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn density_target(&self, size: Extent3d, context: Context) -> DensityTarget {
+        let texture: Texture = context.device().create_texture(&TextureDescriptor {
+            label: Some("Density Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: self.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[self.format],
+        });
+        let view = texture.create_view(&TextureViewDescriptor {
+            label: Some("Density Texture View"),
+            ..Default::default()
+        });
+
+        let bind_group = context.device().create_bind_group(&BindGroupDescriptor {
+            label: Some("Density Colorize Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&self.palette_view),
+                },
+            ],
+        });
+
+        DensityTarget {
+            size,
+            view,
+            bind_group,
+        }
+    }
+}
@@ -0,0 +1,32 @@
+struct RenderSettings {
+    foreground: vec4<f32>,
+    background: vec4<f32>,
+    saturation: f32,
+    point_style: u32,
+}
+
+@group(0) @binding(0) var density: texture_2d<f32>;
+@group(0) @binding(1) var palette: texture_1d<f32>;
+@group(1) @binding(0) var<uniform> settings: RenderSettings;
+
+// a single triangle covering the whole target
+@vertex fn vertex(@builtin(vertex_index) idx: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((idx << 1u) & 2u), f32(idx & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment fn fragment(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let count = textureLoad(density, vec2<u32>(position.xy), 0).r;
+    if count <= 0.0 {
+        return settings.background;
+    }
+
+    // logarithmic, so that sparse regions stay visible next to dense ones
+    let t = clamp(log(1.0 + count) / log(1.0 + settings.saturation), 0.0, 1.0);
+
+    // linear interpolation between the two nearest texels
+    let x = t * f32(textureDimensions(palette) - 1u);
+    let below = textureLoad(palette, u32(floor(x)), 0);
+    let above = textureLoad(palette, u32(ceil(x)), 0);
+    return mix(below, above, fract(x));
+}
@@ -0,0 +1,215 @@
+use std::{collections::HashMap, mem};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{dvec2, DAffine2, DVec2, Vec2};
+use wgpu::{
+    include_wgsl, BlendState, BufferAddress, BufferUsages, Color, ColorTargetState, ColorWrites,
+    FragmentState, MultisampleState, PipelineLayout, PipelineLayoutDescriptor, PrimitiveState,
+    PrimitiveTopology, RenderPass, RenderPipeline, RenderPipelineDescriptor, ShaderModule,
+    TextureFormat, VertexAttribute, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use super::{settings, Camera};
+use crate::{
+    app::Context,
+    buffer::Buffer,
+    map::{DMap, Rect},
+};
+
+/// Outline of a region and of its image under each map, with an arrow showing how each map
+/// rotates and reflects it.
+///
+/// The arrow points along the region's x axis and only has a barb on its y side, which switches
+/// sides under reflections.
+#[derive(Debug, Clone)]
+pub struct MapOverlay {
+    pub region: Rect,
+    pub maps: Vec<DMap>,
+    /// Transform from the coordinates the points are rendered in to those of `region` and
+    /// `maps`, e.g. [`crate::zoom::DeepZoom::frame`].
+    pub frame: DAffine2,
+}
+
+impl MapOverlay {
+    const REGION_COLOR: u32 = 0x999999;
+    // matplotlib's `tab10`
+    const MAP_COLORS: [u32; 10] = [
+        0x1f77b4, 0xff7f0e, 0x2ca02c, 0xd62728, 0x9467bd, 0x8c564b, 0xe377c2, 0x7f7f7f, 0xbcbd22,
+        0x17becf,
+    ];
+
+    pub fn new(region: Rect, maps: impl IntoIterator<Item = DMap>) -> Self {
+        Self {
+            region,
+            maps: maps.into_iter().collect(),
+            frame: DAffine2::IDENTITY,
+        }
+    }
+
+    pub fn with_frame(mut self, frame: DAffine2) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    // pairs of vertices of a line list
+    fn vertices(&self, srgb: bool) -> Vec<OverlayVertex> {
+        let min = self.region.min.as_dvec2();
+        let max = self.region.max.as_dvec2();
+        let center = 0.5 * (min + max);
+        let half_size = 0.5 * (max - min);
+        let tip = center + dvec2(0.6 * half_size.x, 0.0);
+        let corners = [min, dvec2(max.x, min.y), max, dvec2(min.x, max.y)];
+        let segments = [
+            (corners[0], corners[1]),
+            (corners[1], corners[2]),
+            (corners[2], corners[3]),
+            (corners[3], corners[0]),
+            (center, tip),
+            (tip, tip + dvec2(-0.2 * half_size.x, 0.2 * half_size.y)),
+        ];
+
+        let to_local = self.frame.inverse();
+        let shape = |map: DAffine2, color: u32| {
+            let color = to_array(settings::convert(hex_to_color(color), srgb));
+            segments.into_iter().flat_map(move |(start, end)| {
+                [start, end].map(|point: DVec2| OverlayVertex {
+                    position: (to_local * map).transform_point2(point).as_vec2(),
+                    color,
+                })
+            })
+        };
+
+        shape(DAffine2::IDENTITY, Self::REGION_COLOR)
+            .chain(self.maps.iter().enumerate().flat_map(|(idx, map)| {
+                shape(map.map, Self::MAP_COLORS[idx % Self::MAP_COLORS.len()])
+            }))
+            .collect()
+    }
+}
+
+fn hex_to_color(rgb: u32) -> Color {
+    let channel = |shift: u32| f64::from((rgb >> shift) & 0xff) / 255.0;
+    Color {
+        r: channel(16),
+        g: channel(8),
+        b: channel(0),
+        a: 1.0,
+    }
+}
+
+fn to_array(color: Color) -> [f32; 4] {
+    [color.r, color.g, color.b, color.a].map(|value| value as f32)
+}
+
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C)]
+struct OverlayVertex {
+    position: Vec2,
+    color: [f32; 4],
+}
+
+/// Draws a [`MapOverlay`] as lines over the points.
+#[derive(Debug)]
+pub(super) struct Overlay {
+    vertices: Buffer<OverlayVertex>,
+    pipeline_layout: PipelineLayout,
+    shader: ShaderModule,
+    // by sample count
+    pipelines: HashMap<u32, RenderPipeline>,
+}
+
+impl Overlay {
+    pub(super) fn new(overlay: &MapOverlay, srgb: bool, context: Context) -> Self {
+        let pipeline_layout = context
+            .device()
+            .create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Overlay Pipeline Layout"),
+                bind_group_layouts: &[Camera::bind_group_layout(context.borrow())],
+                push_constant_ranges: &[],
+            });
+
+        let shader = context
+            .device()
+            .create_shader_module(include_wgsl!("overlay.wgsl"));
+
+        Self {
+            vertices: Self::vertex_buffer(overlay, srgb, context),
+            pipeline_layout,
+            shader,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    fn vertex_buffer(overlay: &MapOverlay, srgb: bool, context: Context) -> Buffer<OverlayVertex> {
+        Buffer::from_data(
+            &overlay.vertices(srgb),
+            Some("Overlay Vertices"),
+            BufferUsages::VERTEX,
+            context,
+        )
+    }
+
+    pub(super) fn set_overlay(&mut self, overlay: &MapOverlay, srgb: bool, context: Context) {
+        self.vertices = Self::vertex_buffer(overlay, srgb, context);
+    }
+
+    /// Creates the pipeline for targets with `samples` samples per pixel, if it doesn't exist yet.
+    pub(super) fn create_pipeline(
+        &mut self,
+        samples: u32,
+        texture_format: TextureFormat,
+        context: Context,
+    ) {
+        if self.pipelines.contains_key(&samples) {
+            return;
+        }
+
+        const ATTRIBUTES: [VertexAttribute; 2] =
+            wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+        let pipeline = context
+            .device()
+            .create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(&format!("Overlay Render Pipeline ({samples}×)")),
+                layout: Some(&self.pipeline_layout),
+                vertex: VertexState {
+                    module: &self.shader,
+                    buffers: &[VertexBufferLayout {
+                        array_stride: mem::size_of::<OverlayVertex>() as BufferAddress,
+                        step_mode: VertexStepMode::Vertex,
+                        attributes: &ATTRIBUTES,
+                    }],
+                    entry_point: Some("vertex"),
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(FragmentState {
+                    module: &self.shader,
+                    targets: &[Some(ColorTargetState {
+                        format: texture_format,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    })],
+                    entry_point: Some("fragment"),
+                    compilation_options: Default::default(),
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: MultisampleState {
+                    count: samples,
+                    ..Default::default()
+                },
+                multiview: None,
+                cache: None,
+            });
+        self.pipelines.insert(samples, pipeline);
+    }
+
+    pub(super) fn draw(&self, render_pass: &mut RenderPass<'_>, camera: &Camera, samples: u32) {
+        render_pass.set_pipeline(&self.pipelines[&samples]);
+        render_pass.set_vertex_buffer(0, *self.vertices.slice(..));
+        render_pass.set_bind_group(0, &camera.bind_group, &[]);
+        render_pass.draw(0..self.vertices.len_u32(), 0..1);
+    }
+}
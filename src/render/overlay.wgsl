@@ -0,0 +1,23 @@
+// `ViewportClip`, see `render.wgsl`
+struct ViewportClip {
+    scale: vec2<f32>,
+    offset: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> inverse_camera: mat3x3<f32>;
+@group(0) @binding(1) var<uniform> viewport_clip: ViewportClip;
+
+struct Vertex {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex fn vertex(@location(0) position: vec2<f32>, @location(1) color: vec4<f32>) -> Vertex {
+    let clip_position = (inverse_camera * vec3<f32>(position, 1.0)).xy;
+    let viewport_position = clip_position * viewport_clip.scale + viewport_clip.offset;
+    return Vertex(vec4<f32>(viewport_position, 0.0, 1.0), color);
+}
+
+@fragment fn fragment(vertex: Vertex) -> @location(0) vec4<f32> {
+    return vertex.color;
+}
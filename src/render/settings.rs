@@ -0,0 +1,220 @@
+use std::{num::ParseIntError, str::FromStr};
+
+use bytemuck::{Pod, Zeroable};
+use clap::ValueEnum;
+use thiserror::Error;
+use wgpu::{BlendComponent, BlendFactor, BlendOperation, BlendState, Color};
+
+/// How the points are drawn by a [`super::Renderer`].
+///
+/// Colours are in sRGB, and converted to linear when rendering to an sRGB texture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    pub foreground: Color,
+    /// Clear colour of the target, which may be transparent.
+    pub background: Color,
+    pub blend: BlendMode,
+    /// Number of points in a pixel at which the palette reaches its last colour, when rendering
+    /// density with a palette.
+    pub saturation: f32,
+    pub point_style: PointStyle,
+    /// Diameter of the points in pixels, unless drawn with [`PointStyle::Pixel`].
+    pub point_size: f32,
+    /// Samples per pixel for multisample anti-aliasing, 1 to disable it. Ignored when rendering
+    /// density with a palette or with trails.
+    pub samples: u32,
+    /// Fraction of the previous frame kept under the points, leaving trails behind them as they
+    /// move, or `None` to clear every frame. Ignored when rendering density with a palette.
+    pub trails: Option<f32>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            foreground: Color::WHITE,
+            background: Color::BLACK,
+            blend: BlendMode::default(),
+            saturation: 256.0,
+            point_style: PointStyle::default(),
+            point_size: 3.0,
+            samples: 1,
+            trails: None,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn with_foreground(mut self, foreground: Color) -> Self {
+        self.foreground = foreground;
+        self
+    }
+
+    pub fn with_background(mut self, background: Color) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn with_saturation(mut self, saturation: f32) -> Self {
+        self.saturation = saturation;
+        self
+    }
+
+    pub fn with_point_style(mut self, point_style: PointStyle) -> Self {
+        self.point_style = point_style;
+        self
+    }
+
+    pub fn with_point_size(mut self, point_size: f32) -> Self {
+        self.point_size = point_size;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: u32) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    pub fn with_trails(mut self, trails: Option<f32>) -> Self {
+        self.trails = trails.map(|fade| fade.clamp(0.0, 1.0));
+        self
+    }
+
+    pub(super) fn gpu_repr(&self, srgb: bool) -> RenderSettingsUniform {
+        let to_array = |color| {
+            let Color { r, g, b, a } = convert(color, srgb);
+            [r as f32, g as f32, b as f32, a as f32]
+        };
+        RenderSettingsUniform {
+            foreground: to_array(self.foreground),
+            background: to_array(self.background),
+            saturation: self.saturation,
+            point_style: self.point_style as u32,
+            _padding: [0; 2],
+        }
+    }
+
+    pub(super) fn clear_color(&self, srgb: bool) -> Color {
+        convert(self.background, srgb)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C)]
+pub(super) struct RenderSettingsUniform {
+    foreground: [f32; 4],
+    background: [f32; 4],
+    saturation: f32,
+    point_style: u32,
+    _padding: [u32; 2],
+}
+
+/// Footprint of each point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, ValueEnum)]
+pub enum PointStyle {
+    /// A single pixel.
+    #[default]
+    Pixel,
+    /// A disc with an anti-aliased edge.
+    Disc,
+    /// A soft Gaussian splat, best combined with additive or alpha blending.
+    Gaussian,
+}
+
+impl PointStyle {
+    pub(super) fn is_sprite(self) -> bool {
+        self != Self::Pixel
+    }
+}
+
+/// How the colour of a point is combined with what is already drawn underneath.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, ValueEnum)]
+pub enum BlendMode {
+    /// Overwrite, ignoring alpha.
+    #[default]
+    Replace,
+    /// Add the colour weighted by its alpha, so that dense regions get brighter.
+    Additive,
+    /// Regular alpha compositing.
+    Alpha,
+}
+
+impl BlendMode {
+    pub const ALL: [Self; 3] = [Self::Replace, Self::Additive, Self::Alpha];
+
+    pub(super) fn blend_state(self) -> BlendState {
+        match self {
+            Self::Replace => BlendState::REPLACE,
+            Self::Additive => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+            },
+            Self::Alpha => BlendState::ALPHA_BLENDING,
+        }
+    }
+}
+
+pub(super) fn convert(color: Color, srgb: bool) -> Color {
+    if !srgb {
+        return color;
+    }
+
+    let to_linear = |value: f64| {
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    Color {
+        r: to_linear(color.r),
+        g: to_linear(color.g),
+        b: to_linear(color.b),
+        a: color.a,
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum ParseColorError {
+    #[error("expected 6 or 8 hexadecimal digits, e.g. `#ff8000` or `#ff800080`")]
+    Length,
+    #[error(transparent)]
+    Digits(#[from] ParseIntError),
+}
+
+/// A colour written as `#rrggbb` or `#rrggbbaa`, the `#` being optional.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HexColor(pub Color);
+
+impl FromStr for HexColor {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix('#').unwrap_or(s);
+        if !matches!(digits.len(), 6 | 8) || !digits.is_ascii() {
+            return Err(ParseColorError::Length);
+        }
+
+        let channel = |idx: usize| -> Result<f64, ParseIntError> {
+            Ok(f64::from(u8::from_str_radix(&digits[2 * idx..2 * idx + 2], 16)?) / 255.0)
+        };
+        Ok(Self(Color {
+            r: channel(0)?,
+            g: channel(1)?,
+            b: channel(2)?,
+            a: if digits.len() == 8 { channel(3)? } else { 1.0 },
+        }))
+    }
+}
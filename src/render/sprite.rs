@@ -0,0 +1,109 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{vec2, Vec2};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, BufferBindingType, BufferUsages, Extent3d, ShaderStages,
+};
+
+use crate::{app::Context, buffer::Buffer};
+
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C)]
+struct SpriteUniform {
+    radius: Vec2,
+}
+
+/// Uniforms of the pipelines drawing points as quads, whose size in clip space depends on the
+/// size of the target.
+#[derive(Debug)]
+pub(super) struct Sprites {
+    bind_group_layout: BindGroupLayout,
+    point_size: f32,
+    // by width and height of the target, least recently used first
+    bind_groups: Mutex<VecDeque<((u32, u32), BindGroup)>>,
+}
+
+impl Sprites {
+    // enough for every viewport of a split screen, while resizing the window doesn't pile up
+    // a bind group per intermediate size
+    const MAX_SIZES: usize = 8;
+
+    pub(super) fn new(point_size: f32, context: Context) -> Self {
+        let bind_group_layout =
+            context
+                .device()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("Sprite Bind Group Layout"),
+                    entries: &[BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::VERTEX,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+
+        Self {
+            bind_group_layout,
+            point_size,
+            bind_groups: Mutex::default(),
+        }
+    }
+
+    pub(super) fn bind_group_layout(&self) -> &BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub(super) fn set_point_size(&mut self, point_size: f32) {
+        if point_size != self.point_size {
+            self.point_size = point_size;
+            self.bind_groups
+                .get_mut()
+                .expect("failed to lock mutex")
+                .clear();
+        }
+    }
+
+    pub(super) fn bind_group(&self, size: Extent3d, context: Context) -> BindGroup {
+        let key = (size.width, size.height);
+        let mut bind_groups = self.bind_groups.lock().expect("failed to lock mutex");
+        let entry = match bind_groups
+            .iter()
+            .position(|(entry_key, _)| *entry_key == key)
+        {
+            Some(idx) => bind_groups.remove(idx).expect("index should be in bounds"),
+            None => {
+                // a buffer per size, as a single one could only be rewritten between submissions
+                let buffer = Buffer::from_data(
+                    &[SpriteUniform {
+                        radius: self.point_size
+                            / vec2(size.width.max(1) as f32, size.height.max(1) as f32),
+                    }],
+                    Some("Sprite Buffer"),
+                    BufferUsages::UNIFORM,
+                    context.borrow(),
+                );
+                let bind_group = context.device().create_bind_group(&BindGroupDescriptor {
+                    label: Some("Sprite Bind Group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                });
+                if bind_groups.len() >= Self::MAX_SIZES {
+                    bind_groups.pop_front();
+                }
+                (key, bind_group)
+            }
+        };
+        let bind_group = entry.1.clone();
+        bind_groups.push_back(entry);
+        bind_group
+    }
+}
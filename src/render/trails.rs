@@ -0,0 +1,257 @@
+use std::sync::Mutex;
+
+use wgpu::{
+    include_wgsl, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendComponent,
+    BlendFactor, BlendOperation, BlendState, Color, ColorTargetState, ColorWrites, CommandEncoder,
+    Extent3d, FragmentState, LoadOp, Operations, PipelineLayoutDescriptor, PrimitiveState,
+    PrimitiveTopology, RenderPass, RenderPassColorAttachment, RenderPassDescriptor,
+    RenderPassTimestampWrites, RenderPipeline, RenderPipelineDescriptor, ShaderModule,
+    ShaderStages, StoreOp, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
+    TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+use crate::app::Context;
+
+/// Format of the texture that points accumulate in, see [`TrailPass`].
+///
+/// Fading an 8-bit texture would leave faint points stuck where rounding cancels out the fade.
+pub(super) const TRAIL_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// Renders points into a texture that fades instead of being cleared every frame, leaving trails
+/// behind moving points, then composites it over the background of the target.
+#[derive(Debug)]
+pub(super) struct TrailPass {
+    fade_pipeline: RenderPipeline,
+    composite_pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    target: Mutex<Option<TrailTarget>>,
+}
+
+#[derive(Debug)]
+struct TrailTarget {
+    size: Extent3d,
+    view: TextureView,
+    bind_group: BindGroup,
+    // nothing has been drawn into it yet, so it has to be cleared first
+    fresh: bool,
+}
+
+impl TrailPass {
+    pub(super) fn new(texture_format: TextureFormat, context: Context) -> Self {
+        let bind_group_layout =
+            context
+                .device()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("Trail Composite Bind Group Layout"),
+                    entries: &[BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: false },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    }],
+                });
+
+        let shader = context
+            .device()
+            .create_shader_module(include_wgsl!("trails.wgsl"));
+
+        // scales the texture by the blend constant
+        let fade_blend = BlendComponent {
+            src_factor: BlendFactor::Zero,
+            dst_factor: BlendFactor::Constant,
+            operation: BlendOperation::Add,
+        };
+        let fade_pipeline = Self::pipeline(
+            "Trail Fade",
+            &[],
+            &shader,
+            "fade",
+            ColorTargetState {
+                format: TRAIL_FORMAT,
+                blend: Some(BlendState {
+                    color: fade_blend,
+                    alpha: fade_blend,
+                }),
+                write_mask: ColorWrites::ALL,
+            },
+            context.borrow(),
+        );
+        let composite_pipeline = Self::pipeline(
+            "Trail Composite",
+            &[&bind_group_layout],
+            &shader,
+            "composite",
+            ColorTargetState {
+                format: texture_format,
+                blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+            },
+            context,
+        );
+
+        Self {
+            fade_pipeline,
+            composite_pipeline,
+            bind_group_layout,
+            target: Mutex::new(None),
+        }
+    }
+
+    fn pipeline(
+        name: &str,
+        bind_group_layouts: &[&BindGroupLayout],
+        shader: &ShaderModule,
+        fragment_entry_point: &str,
+        target: ColorTargetState,
+        context: Context,
+    ) -> RenderPipeline {
+        let layout = context
+            .device()
+            .create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some(&format!("{name} Pipeline Layout")),
+                bind_group_layouts,
+                push_constant_ranges: &[],
+            });
+        context
+            .device()
+            .create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(&format!("{name} Render Pipeline")),
+                layout: Some(&layout),
+                vertex: VertexState {
+                    module: shader,
+                    buffers: &[],
+                    entry_point: Some("vertex"),
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(FragmentState {
+                    module: shader,
+                    targets: &[Some(target)],
+                    entry_point: Some(fragment_entry_point),
+                    compilation_options: Default::default(),
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleList,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+    }
+
+    /// Forgets the trails, e.g. after the view moved.
+    pub(super) fn clear(&mut self) {
+        *self.target.get_mut().expect("failed to lock mutex") = None;
+    }
+
+    /// Encodes fading the trails by `fade` and drawing the points over them with `draw_points`,
+    /// then compositing them over `clear_color` in the target and drawing `draw_overlay` on top.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn encode(
+        &self,
+        encoder: &mut CommandEncoder,
+        draw_points: impl FnOnce(&mut RenderPass<'_>),
+        draw_overlay: impl FnOnce(&mut RenderPass<'_>),
+        fade: f32,
+        clear_color: Color,
+        target: &TextureView,
+        size: Extent3d,
+        timestamp_writes: [Option<RenderPassTimestampWrites>; 2],
+        context: Context,
+    ) {
+        let mut trail_target = self.target.lock().expect("failed to lock mutex");
+        let trail_target = match &mut *trail_target {
+            Some(trail_target) if trail_target.size == size => trail_target,
+            trail_target => trail_target.insert(self.trail_target(size, context)),
+        };
+        let [accumulate_timestamp_writes, composite_timestamp_writes] = timestamp_writes;
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Trail Accumulate Render Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &trail_target.view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: if trail_target.fresh {
+                            LoadOp::Clear(Color::TRANSPARENT)
+                        } else {
+                            LoadOp::Load
+                        },
+                        store: StoreOp::Store,
+                    },
+                })],
+                timestamp_writes: accumulate_timestamp_writes,
+                ..Default::default()
+            });
+            let fade = f64::from(fade);
+            render_pass.set_pipeline(&self.fade_pipeline);
+            render_pass.set_blend_constant(Color {
+                r: fade,
+                g: fade,
+                b: fade,
+                a: fade,
+            });
+            render_pass.draw(0..3, 0..1);
+            draw_points(&mut render_pass);
+        }
+        trail_target.fresh = false;
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Trail Composite Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(clear_color),
+                    store: StoreOp::Store,
+                },
+            })],
+            timestamp_writes: composite_timestamp_writes,
+            ..Default::default()
+        });
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, &trail_target.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        draw_overlay(&mut render_pass);
+    }
+
+    fn trail_target(&self, size: Extent3d, context: Context) -> TrailTarget {
+        let texture = context.device().create_texture(&TextureDescriptor {
+            label: Some("Trail Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TRAIL_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[TRAIL_FORMAT],
+        });
+        let view = texture.create_view(&TextureViewDescriptor {
+            label: Some("Trail Texture View"),
+            ..Default::default()
+        });
+
+        let bind_group = context.device().create_bind_group(&BindGroupDescriptor {
+            label: Some("Trail Composite Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&view),
+            }],
+        });
+
+        TrailTarget {
+            size,
+            view,
+            bind_group,
+            fresh: true,
+        }
+    }
+}
@@ -0,0 +1,17 @@
+@group(0) @binding(0) var trails: texture_2d<f32>;
+
+// a single triangle covering the whole target
+@vertex fn vertex(@builtin(vertex_index) idx: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((idx << 1u) & 2u), f32(idx & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// only the blend constant matters, scaling what is already drawn
+@fragment fn fade() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0);
+}
+
+// premultiplied by alpha, to be blended over the background
+@fragment fn composite(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureLoad(trails, vec2<u32>(position.xy), 0);
+}
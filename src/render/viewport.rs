@@ -0,0 +1,89 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{vec2, Vec2};
+use wgpu::{Extent3d, RenderPass};
+
+/// Sub-rectangle of a render target that a scene is drawn into, in pixels from the top left
+/// corner of the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    /// The whole of a target of `size`.
+    pub fn full(size: Extent3d) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width: size.width,
+            height: size.height,
+        }
+    }
+
+    pub fn size(&self) -> Extent3d {
+        Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: 1,
+        }
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        (f64::from(self.x)..f64::from(self.x + self.width)).contains(&x)
+            && (f64::from(self.y)..f64::from(self.y + self.height)).contains(&y)
+    }
+
+    /// Restricts drawing to the part of the viewport inside `target`, returning how to draw the
+    /// scene at the size of the whole viewport, or `None` if none of it is inside.
+    ///
+    /// wgpu rejects viewports reaching past the target, so a partly visible one is set to its
+    /// visible part and the scene is kept from being squashed into it by [`ViewportClip`].
+    /// Targets of unknown size are drawn to all over.
+    pub(super) fn apply(
+        &self,
+        render_pass: &mut RenderPass<'_>,
+        target: Option<Extent3d>,
+    ) -> Option<ViewportClip> {
+        let Some(target) = target else {
+            return Some(ViewportClip::IDENTITY);
+        };
+        let x = self.x.min(target.width);
+        let y = self.y.min(target.height);
+        let width = self.width.min(target.width - x);
+        let height = self.height.min(target.height - y);
+        if width == 0 || height == 0 {
+            return None;
+        }
+        render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+        render_pass.set_scissor_rect(x, y, width, height);
+
+        // only the right and bottom edges are cut off, clip space points up
+        let scale = vec2(
+            self.width as f32 / width as f32,
+            self.height as f32 / height as f32,
+        );
+        Some(ViewportClip {
+            scale,
+            offset: vec2(scale.x - 1.0, 1.0 - scale.y),
+        })
+    }
+}
+
+/// Maps clip space of a whole [`Viewport`] to that of its part inside the target, which
+/// positions are transformed by after the camera.
+#[derive(Debug, Clone, Copy, PartialEq, Zeroable, Pod)]
+#[repr(C)]
+pub(super) struct ViewportClip {
+    scale: Vec2,
+    offset: Vec2,
+}
+
+impl ViewportClip {
+    pub(super) const IDENTITY: Self = Self {
+        scale: Vec2::ONE,
+        offset: Vec2::ZERO,
+    };
+}
@@ -1,98 +1,335 @@
-use std::{iter, mem, num::NonZero};
+use std::{
+    borrow::Cow,
+    future::Future,
+    iter, mem,
+    num::NonZero,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use bytemuck::{Pod, Zeroable};
-use glam::{Mat3, Vec2};
+use clap::ValueEnum;
+use color_eyre::eyre::Result;
+use futures::FutureExt;
+use glam::{vec2, Affine2, DAffine2, DMat3, Mat3, Vec2};
 
 use itertools::Itertools;
+use log::warn;
+use rand::Rng;
 use wgpu::{
     include_wgsl, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
     BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BufferBinding,
     BufferBindingType, BufferUsages, CommandEncoderDescriptor, ComputePassDescriptor,
-    ComputePipeline, ComputePipelineDescriptor, PipelineCompilationOptions,
-    PipelineLayoutDescriptor, ShaderStages,
+    ComputePipeline, ComputePipelineDescriptor, Features, PipelineCompilationOptions,
+    PipelineLayoutDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
 };
 
 use crate::{
     app::Context,
     buffer::Buffer,
-    map::Map,
-    util::{SyncingFuture, WgpuMat3x3},
+    map::{DMap, Map, Rect},
+    profiler::{ProfileScope, Profiler},
+    state::SimulationState,
+    util::{SyncingFuture, WgpuDMat3x3, WgpuMat3x3},
+    zoom::DeepZoom,
 };
 
+mod seed;
+mod statistics;
+
+pub use statistics::Statistics;
+use statistics::StatisticsPass;
+
+// the lowest `max_compute_workgroups_per_dimension` that adapters may have
+const MAX_WORKGROUPS_PER_DIMENSION: u32 = u16::MAX as u32;
+
 #[derive(Debug, Clone, Copy, Zeroable, Pod)]
 #[repr(C)]
 pub struct Point {
     pub position: Vec2,
 }
 
+impl Point {
+    /// A point chosen uniformly over `region`.
+    pub fn random_in(region: &Rect, rng: &mut impl Rng) -> Self {
+        Self {
+            position: vec2(
+                rng.random_range(region.min.x..=region.max.x),
+                rng.random_range(region.min.y..=region.max.y),
+            ),
+        }
+    }
+}
+
+/// Lookup table from uniformly distributed indices to map indices, following the maps'
+/// probability weights.
+///
+/// Weights that aren't positive count as zero, and the maps are picked uniformly if none is.
+pub(crate) fn map_index_array(probability_weights: &[f32]) -> Vec<u32> {
+    const MAP_INDEX_ARRAY_LEN: usize = 144;
+
+    let mut probability_weights = probability_weights
+        .iter()
+        .map(|&weight| if weight > 0.0 { weight } else { 0.0 })
+        .collect_vec();
+    let mut probability_weight_sum: f32 = probability_weights.iter().sum();
+    if !(probability_weight_sum.is_finite() && probability_weight_sum > 0.0) {
+        probability_weights.fill(1.0);
+        probability_weight_sum = probability_weights.len() as f32;
+    }
+    let probabilities = probability_weights
+        .iter()
+        .map(|weight| weight / probability_weight_sum);
+    let cumulated_probabilities = probabilities.scan(0.0, |accumulator, probability| {
+        *accumulator += probability;
+        Some((*accumulator * MAP_INDEX_ARRAY_LEN as f32).round() as usize)
+    });
+    iter::once(0)
+        .chain(cumulated_probabilities)
+        .tuple_windows()
+        .enumerate()
+        .flat_map(|(i, (p, q))| iter::repeat_n(i as u32, q - p))
+        .collect()
+}
+
+/// Floating point precision of the simulation's arithmetic.
+///
+/// Points are always stored in single precision, but with [`Precision::Double`] the maps are
+/// stored and applied in double precision, which matters when they are conjugated for deep zooms
+/// (see [`crate::zoom::DeepZoom`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Precision {
+    #[default]
+    Single,
+    /// Requires [`Features::SHADER_F64`].
+    Double,
+}
+
+impl Precision {
+    fn maps_gpu_repr(self, maps: &[DMap]) -> Vec<u8> {
+        match self {
+            Self::Single => {
+                let maps = maps
+                    .iter()
+                    .map(|map| {
+                        let mat: Mat3 = map.map.as_affine2().into();
+                        WgpuMat3x3::from(mat)
+                    })
+                    .collect_vec();
+                bytemuck::cast_slice(&maps).to_vec()
+            }
+            Self::Double => {
+                let maps = maps
+                    .iter()
+                    .map(|map| {
+                        let mat: DMat3 = map.map.into();
+                        WgpuDMat3x3::from(mat)
+                    })
+                    .collect_vec();
+                bytemuck::cast_slice(&maps).to_vec()
+            }
+        }
+    }
+
+    fn shader_source(self) -> &'static str {
+        match self {
+            Self::Single => concat!(
+                include_str!("sim/random.wgsl"),
+                include_str!("sim/common.wgsl"),
+                include_str!("sim.wgsl")
+            ),
+            Self::Double => concat!(
+                include_str!("sim/random.wgsl"),
+                include_str!("sim/common.wgsl"),
+                include_str!("sim/f64.wgsl")
+            ),
+        }
+    }
+}
+
+/// How points that escaped the simulation are put back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Respawn {
+    /// Uniformly over the escape region.
+    #[default]
+    Uniform,
+    /// On top of another live point, chosen at random.
+    LivePoint,
+}
+
+/// Escape detection parameters of a [`Simulation`].
+///
+/// A point escapes when it is not finite or when it lies further than `radius` from the center of
+/// `region`.
+#[derive(Debug, Clone, Copy)]
+pub struct Escape {
+    pub region: Rect,
+    pub radius: f32,
+    pub respawn: Respawn,
+}
+
+impl Escape {
+    const DEFAULT_RADIUS_FACTOR: f32 = 100.0;
+
+    pub fn new(region: Rect) -> Self {
+        let half_diagonal = 0.5 * (region.max - region.min).length();
+        Self {
+            region,
+            radius: Self::DEFAULT_RADIUS_FACTOR * half_diagonal,
+            respawn: Respawn::default(),
+        }
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn with_respawn(mut self, respawn: Respawn) -> Self {
+        self.respawn = respawn;
+        self
+    }
+
+    fn gpu_repr(&self, seed: u32) -> EscapeUniform {
+        EscapeUniform {
+            min: self.region.min,
+            max: self.region.max,
+            radius: self.radius,
+            respawn: self.respawn as u32,
+            seed,
+            _padding: 0,
+        }
+    }
+}
+
+impl Default for Escape {
+    fn default() -> Self {
+        Self::new(Rect {
+            min: Vec2::NEG_ONE,
+            max: Vec2::ONE,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C)]
+struct EscapeUniform {
+    min: Vec2,
+    max: Vec2,
+    radius: f32,
+    respawn: u32,
+    seed: u32,
+    _padding: u32,
+}
+
 #[derive(Debug)]
 pub struct Simulation<P: AsRef<Buffer<Point>>> {
     points: P,
+    point_bind_group_layout: BindGroupLayout,
     point_bind_groups: Vec<(BindGroup, u32)>,
-    _maps: Buffer<WgpuMat3x3>,
+    _chunk_offsets: Buffer<u32>,
+    precision: Precision,
+    maps: Vec<DMap>,
+    map_buffer: Buffer<u8>,
+    map_indices: Buffer<u32>,
+    map_bind_group_layout: BindGroupLayout,
     map_bind_group: BindGroup,
-    _map_indices: Buffer<u32>,
+    escape: Escape,
+    escape_buffer: Buffer<EscapeUniform>,
+    respawn_count: Buffer<u32>,
+    // what `respawn_count` was when last taken
+    respawn_count_taken: Buffer<u32>,
+    escape_bind_group: BindGroup,
+    step: AtomicUsize,
+    seed: u32,
     pipeline: ComputePipeline,
+    transform_buffer: Buffer<WgpuMat3x3>,
+    transform_bind_group: BindGroup,
+    transform_pipeline: ComputePipeline,
+    statistics: Option<StatisticsPass>,
+    statistics_step: AtomicUsize,
+    statistics_pending: AtomicBool,
+    profiler: Option<Arc<Profiler>>,
 }
 
 impl<P: AsRef<Buffer<Point>>> Simulation<P> {
     pub fn new(points: P, maps: &[Map], context: Context) -> Self {
+        let maps = maps.iter().copied().map(DMap::from).collect_vec();
+        Self::with_precision(points, &maps, Precision::Single, context)
+    }
+
+    /// Falls back to [`Precision::Single`] when double precision isn't supported by the device.
+    pub fn with_precision(
+        points: P,
+        maps: &[DMap],
+        mut precision: Precision,
+        context: Context,
+    ) -> Self {
+        if precision == Precision::Double
+            && !context.device().features().contains(Features::SHADER_F64)
+        {
+            warn!("double precision shaders aren't supported, falling back to single precision");
+            precision = Precision::Single;
+        }
+
         let points_buf = points.as_ref();
 
-        let (point_bind_group_layout, point_bind_group) =
-            Self::point_bind_groups(points_buf, context.borrow());
+        let point_bind_group_layout = Self::point_bind_group_layout(context.borrow());
+        let (point_bind_groups, chunk_offsets) =
+            Self::point_bind_groups(&point_bind_group_layout, points_buf, context.borrow());
 
-        let maps_gpu_repr: Vec<WgpuMat3x3> = maps
-            .iter()
-            .map(|map| {
-                let mat: Mat3 = map.map.into();
-                WgpuMat3x3::from(mat)
-            })
-            .collect();
-        let map_buffer = Buffer::from_data(
-            &maps_gpu_repr,
-            Some("Maps"),
-            BufferUsages::STORAGE,
+        let (map_buffer, map_indices) = Self::map_buffers(maps, precision, context.borrow());
+
+        let map_bind_group_layout = Self::map_bind_group_layout(context.borrow());
+        let map_bind_group = Self::map_bind_group(
+            &map_bind_group_layout,
+            &map_buffer,
+            &map_indices,
             context.borrow(),
         );
 
-        const MAP_INDEX_ARRAY_LEN: usize = 144;
-
-        let probability_weight_sum: f32 = maps.iter().map(|map| map.probability_weight).sum();
-        let probabilities = maps
-            .iter()
-            .map(|map| map.probability_weight / probability_weight_sum);
-        let cumulated_probabilities = probabilities.scan(0.0, |accumulator, probability| {
-            *accumulator += probability;
-            Some((*accumulator * MAP_INDEX_ARRAY_LEN as f32).round() as usize)
-        });
-        let map_index_array: Vec<u32> = iter::once(0)
-            .chain(cumulated_probabilities)
-            .tuple_windows()
-            .enumerate()
-            .flat_map(|(i, (p, q))| iter::repeat_n(i as u32, q - p))
-            .collect();
-        let map_indices = Buffer::from_data(
-            &map_index_array,
-            Some("Map Indices"),
-            BufferUsages::STORAGE,
+        let escape = Escape::default();
+        let escape_buffer = Buffer::from_data(
+            &[escape.gpu_repr(0)],
+            Some("Simulation Escape Parameters"),
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
             context.borrow(),
         );
-
-        let (map_bind_group_layout, map_bind_group) =
-            Self::map_bind_group(&map_buffer, &map_indices, context.borrow());
+        let respawn_count = Buffer::new(
+            1,
+            Some("Simulation Respawn Count"),
+            BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+            context.borrow(),
+        );
+        let respawn_count_taken = Buffer::new(
+            1,
+            Some("Simulation Taken Respawn Count"),
+            BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+            context.borrow(),
+        );
+        let (escape_bind_group_layout, escape_bind_group) =
+            Self::escape_bind_group(&escape_buffer, &respawn_count, context.borrow());
 
         let pipeline_layout = context
             .device()
             .create_pipeline_layout(&PipelineLayoutDescriptor {
                 label: Some("Simulation Compute Pipeline Layout"),
-                bind_group_layouts: &[&map_bind_group_layout, &point_bind_group_layout],
+                bind_group_layouts: &[
+                    &map_bind_group_layout,
+                    &point_bind_group_layout,
+                    &escape_bind_group_layout,
+                ],
                 push_constant_ranges: &[],
             });
 
         let shader = context
             .device()
-            .create_shader_module(include_wgsl!("sim.wgsl"));
+            .create_shader_module(ShaderModuleDescriptor {
+                label: Some("Simulation Shader"),
+                source: ShaderSource::Wgsl(Cow::Borrowed(precision.shader_source())),
+            });
 
         let pipeline = context
             .device()
@@ -105,44 +342,314 @@ impl<P: AsRef<Buffer<Point>>> Simulation<P> {
                 cache: None,
             });
 
+        let transform_buffer = Buffer::new(
+            1,
+            Some("Simulation Point Transform"),
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            context.borrow(),
+        );
+        let (transform_bind_group_layout, transform_bind_group) =
+            Self::transform_bind_group(&transform_buffer, context.borrow());
+
+        let transform_pipeline_layout =
+            context
+                .device()
+                .create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("Simulation Point Transform Pipeline Layout"),
+                    bind_group_layouts: &[&transform_bind_group_layout, &point_bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+
+        let transform_shader = context
+            .device()
+            .create_shader_module(include_wgsl!("sim/transform.wgsl"));
+
+        let transform_pipeline =
+            context
+                .device()
+                .create_compute_pipeline(&ComputePipelineDescriptor {
+                    label: Some("Simulation Point Transform Pipeline"),
+                    layout: Some(&transform_pipeline_layout),
+                    module: &transform_shader,
+                    entry_point: Some("transform_points"),
+                    compilation_options: PipelineCompilationOptions::default(),
+                    cache: None,
+                });
+
         Self {
             points,
-            _maps: map_buffer,
-            _map_indices: map_indices,
+            precision,
+            maps: maps.to_vec(),
+            map_buffer,
+            map_indices,
             pipeline,
-            point_bind_groups: point_bind_group,
+            point_bind_group_layout,
+            point_bind_groups,
+            _chunk_offsets: chunk_offsets,
+            map_bind_group_layout,
             map_bind_group,
+            escape,
+            escape_buffer,
+            respawn_count,
+            respawn_count_taken,
+            escape_bind_group,
+            step: AtomicUsize::new(0),
+            seed: 0,
+            transform_buffer,
+            transform_bind_group,
+            transform_pipeline,
+            statistics: None,
+            statistics_step: AtomicUsize::new(0),
+            statistics_pending: AtomicBool::new(false),
+            profiler: None,
         }
     }
 
+    pub fn precision(&self) -> Precision {
+        self.precision
+    }
+
+    pub fn maps(&self) -> &[DMap] {
+        &self.maps
+    }
+
+    /// Number of steps taken since the simulation was created.
+    pub fn step_count(&self) -> usize {
+        self.step.load(Ordering::Relaxed)
+    }
+
+    /// Seed of the randomness used to respawn escaped points and to reset them, on top of the step
+    /// count. Maps are picked by hashing each point's position, independently of it.
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// Changes the seed, e.g. to get a different sequence of respawned points.
+    pub fn set_seed(&mut self, seed: u32, context: Context) {
+        self.seed = seed;
+        self.set_escape(self.escape, context);
+    }
+
+    fn random_seed(&self, step: usize) -> u32 {
+        (step as u32).wrapping_add(self.seed.wrapping_mul(0x9e37_79b9))
+    }
+
+    pub fn escape(&self) -> &Escape {
+        &self.escape
+    }
+
+    /// Sets how escaped points are detected and respawned.
+    pub fn set_escape(&mut self, escape: Escape, context: Context) {
+        self.escape = escape;
+        self.escape_buffer.write(
+            0,
+            &[escape.gpu_repr(self.random_seed(self.step_count()))],
+            context,
+        );
+    }
+
+    /// Number of points respawned since the last call, or since the simulation was created.
+    pub fn take_respawn_count(
+        &self,
+        context: Context,
+    ) -> impl Future<Output = Result<u32>> + 'static {
+        let mut encoder = context
+            .device()
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Simulation Respawn Count Command Encoder"),
+            });
+        encoder.copy_buffer_to_buffer(
+            &self.respawn_count,
+            0,
+            &self.respawn_count_taken,
+            0,
+            self.respawn_count.size(),
+        );
+        encoder.clear_buffer(&self.respawn_count, 0, None);
+        context
+            .queue()
+            .submit(iter::once(encoder.finish()))
+            .ignore();
+
+        self.respawn_count_taken
+            .download(context)
+            .map(|count| Ok(count?[0]))
+    }
+
+    /// Replaces the maps of the simulation, keeping the current points.
+    ///
+    /// The GPU buffers are rewritten in place when they are large enough, and
+    /// reallocated otherwise (e.g. when maps are added).
+    pub fn set_maps(&mut self, maps: &[Map], context: Context) {
+        let maps = maps.iter().copied().map(DMap::from).collect_vec();
+        self.set_dmaps(&maps, context);
+    }
+
+    /// Same as [`Simulation::set_maps`], without losing precision with [`Precision::Double`].
+    pub fn set_dmaps(&mut self, maps: &[DMap], context: Context) {
+        let maps_gpu_repr = self.precision.maps_gpu_repr(maps);
+        let probability_weights = maps.iter().map(|map| map.probability_weight).collect_vec();
+        let map_index_array = map_index_array(&probability_weights);
+
+        self.maps = maps.to_vec();
+
+        if maps_gpu_repr.len() <= self.map_buffer.len()
+            && map_index_array.len() == self.map_indices.len()
+        {
+            self.map_buffer.write(0, &maps_gpu_repr, context.borrow());
+            self.map_indices.write(0, &map_index_array, context);
+            return;
+        }
+
+        let (map_buffer, map_indices) = Self::map_buffers(maps, self.precision, context.borrow());
+        self.map_bind_group = Self::map_bind_group(
+            &self.map_bind_group_layout,
+            &map_buffer,
+            &map_indices,
+            context,
+        );
+        self.map_buffer = map_buffer;
+        self.map_indices = map_indices;
+    }
+
+    fn map_buffers(
+        maps: &[DMap],
+        precision: Precision,
+        context: Context,
+    ) -> (Buffer<u8>, Buffer<u32>) {
+        let map_buffer = Buffer::from_data(
+            &precision.maps_gpu_repr(maps),
+            Some("Maps"),
+            BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            context.borrow(),
+        );
+
+        let probability_weights = maps.iter().map(|map| map.probability_weight).collect_vec();
+        let map_indices = Buffer::from_data(
+            &map_index_array(&probability_weights),
+            Some("Map Indices"),
+            BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            context.borrow(),
+        );
+
+        (map_buffer, map_indices)
+    }
+
+    fn map_bind_group_layout(context: Context) -> BindGroupLayout {
+        context
+            .device()
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Simulation Compute Pipeline Bind Group Layout for Linear Maps"),
+                entries: &[
+                    // maps
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // map indices
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            })
+    }
+
     fn map_bind_group(
-        maps: &Buffer<WgpuMat3x3>,
+        layout: &BindGroupLayout,
+        maps: &Buffer<u8>,
         map_indices: &Buffer<u32>,
         context: Context,
+    ) -> BindGroup {
+        context.device().create_bind_group(&BindGroupDescriptor {
+            label: Some("Simulation Compute Pipeline Bind Group for Linear Maps"),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: maps.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: map_indices.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    fn transform_bind_group(
+        transform: &Buffer<WgpuMat3x3>,
+        context: Context,
+    ) -> (BindGroupLayout, BindGroup) {
+        let transform_bind_group_layout =
+            context
+                .device()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("Simulation Point Transform Bind Group Layout"),
+                    entries: &[BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+
+        let transform_bind_group = context.device().create_bind_group(&BindGroupDescriptor {
+            label: Some("Simulation Point Transform Bind Group"),
+            layout: &transform_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: transform.as_entire_binding(),
+            }],
+        });
+
+        (transform_bind_group_layout, transform_bind_group)
+    }
+
+    fn escape_bind_group(
+        escape: &Buffer<EscapeUniform>,
+        respawn_count: &Buffer<u32>,
+        context: Context,
     ) -> (BindGroupLayout, BindGroup) {
-        let map_bind_group_layout =
+        let escape_bind_group_layout =
             context
                 .device()
                 .create_bind_group_layout(&BindGroupLayoutDescriptor {
-                    label: Some("Simulation Compute Pipeline Bind Group Layout for Linear Maps"),
+                    label: Some("Simulation Compute Pipeline Bind Group Layout for Escapes"),
                     entries: &[
-                        // maps
+                        // escape parameters
                         BindGroupLayoutEntry {
                             binding: 0,
                             visibility: ShaderStages::COMPUTE,
                             ty: BindingType::Buffer {
-                                ty: BufferBindingType::Storage { read_only: true },
+                                ty: BufferBindingType::Uniform,
                                 has_dynamic_offset: false,
                                 min_binding_size: None,
                             },
                             count: None,
                         },
-                        // map indices
+                        // respawn count
                         BindGroupLayoutEntry {
                             binding: 1,
                             visibility: ShaderStages::COMPUTE,
                             ty: BindingType::Buffer {
-                                ty: BufferBindingType::Storage { read_only: true },
+                                ty: BufferBindingType::Storage { read_only: false },
                                 has_dynamic_offset: false,
                                 min_binding_size: None,
                             },
@@ -151,28 +658,72 @@ impl<P: AsRef<Buffer<Point>>> Simulation<P> {
                     ],
                 });
 
-        let map_bind_group = context.device().create_bind_group(&BindGroupDescriptor {
-            label: Some("Simulation Compute Pipeline Bind Group for Linear Maps"),
-            layout: &map_bind_group_layout,
+        let escape_bind_group = context.device().create_bind_group(&BindGroupDescriptor {
+            label: Some("Simulation Compute Pipeline Bind Group for Escapes"),
+            layout: &escape_bind_group_layout,
             entries: &[
                 BindGroupEntry {
                     binding: 0,
-                    resource: maps.as_entire_binding(),
+                    resource: escape.as_entire_binding(),
                 },
                 BindGroupEntry {
                     binding: 1,
-                    resource: map_indices.as_entire_binding(),
+                    resource: respawn_count.as_entire_binding(),
                 },
             ],
         });
 
-        (map_bind_group_layout, map_bind_group)
+        (escape_bind_group_layout, escape_bind_group)
+    }
+
+    fn point_bind_group_layout(context: Context) -> BindGroupLayout {
+        context
+            .device()
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Simulation Compute Pipeline Bind Group Layout for Points"),
+                entries: &[
+                    // points
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // chunk offset and point count
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // all points
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            })
     }
 
     fn point_bind_groups(
+        point_bind_group_layout: &BindGroupLayout,
         points: &Buffer<Point>,
         context: Context,
-    ) -> (BindGroupLayout, Vec<(BindGroup, u32)>) {
+    ) -> (Vec<(BindGroup, u32)>, Buffer<u32>) {
         const MAX_WORKGROUPS_PER_DISPATCH_UNALIGNED: u32 = u16::MAX as u32;
         let alignment = context
             .device()
@@ -184,55 +735,127 @@ impl<P: AsRef<Buffer<Point>>> Simulation<P> {
         let n_max = points.len_u32() / max_workgroups_per_dispatch;
         let rem = points.len_u32() % max_workgroups_per_dispatch;
 
-        let point_bind_group_layout =
-            context
-                .device()
-                .create_bind_group_layout(&BindGroupLayoutDescriptor {
-                    label: Some("Simulation Compute Pipeline Bind Group Layout for Points"),
-                    entries: &[BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: ShaderStages::COMPUTE,
-                        ty: BindingType::Buffer {
-                            ty: BufferBindingType::Storage { read_only: false },
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    }],
-                });
-
-        let point_bind_groups = iter::repeat_n(max_workgroups_per_dispatch, n_max as usize)
+        let chunks = iter::repeat_n(max_workgroups_per_dispatch, n_max as usize)
             .chain([rem])
             .scan(0, |start, len| {
                 let this_start = *start;
                 *start += len;
                 Some((this_start, len))
             })
+            .collect_vec();
+
+        // respawning samples all points, as far as a single binding reaches
+        let max_binding_len = context.device().limits().max_storage_buffer_binding_size
+            / mem::size_of::<Point>() as u32;
+        let all_points_len = points.len_u32().min(max_binding_len);
+
+        // each chunk's offset is stored in its own uniform-aligned slot, followed by the number of
+        // points respawning samples from
+        let chunk_offset_stride = context
+            .device()
+            .limits()
+            .min_uniform_buffer_offset_alignment
+            / mem::size_of::<u32>() as u32;
+        let chunk_offset_data = chunks
+            .iter()
+            .flat_map(|&(start, _)| {
+                [start, all_points_len]
+                    .into_iter()
+                    .chain(iter::repeat_n(0, chunk_offset_stride as usize - 2))
+            })
+            .collect_vec();
+        let chunk_offsets = Buffer::from_data(
+            &chunk_offset_data,
+            Some("Simulation Chunk Offsets"),
+            BufferUsages::UNIFORM,
+            context.borrow(),
+        );
+
+        let point_bind_groups = chunks
+            .into_iter()
             .enumerate()
             .map(|(idx, (start, len))| {
                 let bind_group = context.device().create_bind_group(&BindGroupDescriptor {
                     label: Some(&format!(
                         "Simulation Compute Pipeline Bind Group for Points (Chunk #{idx})"
                     )),
-                    layout: &point_bind_group_layout,
-                    entries: &[BindGroupEntry {
-                        binding: 0,
-                        resource: BindingResource::Buffer(BufferBinding {
-                            buffer: points,
-                            offset: u64::from(start) * mem::size_of::<Point>() as u64,
-                            size: NonZero::new(u64::from(len) * mem::size_of::<Point>() as u64),
-                        }),
-                    }],
+                    layout: point_bind_group_layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::Buffer(BufferBinding {
+                                buffer: points,
+                                offset: u64::from(start) * mem::size_of::<Point>() as u64,
+                                size: NonZero::new(u64::from(len) * mem::size_of::<Point>() as u64),
+                            }),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::Buffer(BufferBinding {
+                                buffer: &chunk_offsets,
+                                offset: u64::from(idx as u32 * chunk_offset_stride)
+                                    * mem::size_of::<u32>() as u64,
+                                size: NonZero::new(2 * mem::size_of::<u32>() as u64),
+                            }),
+                        },
+                        BindGroupEntry {
+                            binding: 2,
+                            resource: BindingResource::Buffer(BufferBinding {
+                                buffer: points,
+                                offset: 0,
+                                size: NonZero::new(
+                                    u64::from(all_points_len) * mem::size_of::<Point>() as u64,
+                                ),
+                            }),
+                        },
+                    ],
                 });
                 (bind_group, len)
             })
             .collect();
 
-        (point_bind_group_layout, point_bind_groups)
+        (point_bind_groups, chunk_offsets)
     }
 
     pub fn step(&self, context: Context<'_>) -> impl SyncingFuture {
-        let commands =
+        let step = self.step.fetch_add(1, Ordering::Relaxed) + 1;
+        self.escape_buffer.write(
+            0,
+            &[self.escape.gpu_repr(self.random_seed(step))],
+            context.borrow(),
+        );
+
+        let statistics = self
+            .statistics
+            .as_ref()
+            .filter(|statistics| step % statistics.interval == 0);
+
+        let save_previous_points = statistics.map(|statistics| {
+            let points = self.points.as_ref();
+            let mut encoder = context
+                .device()
+                .create_command_encoder(&CommandEncoderDescriptor {
+                    label: Some("Simulation Statistics Previous Points Command Encoder"),
+                });
+            encoder.copy_buffer_to_buffer(points, 0, &statistics.previous_points, 0, points.size());
+            encoder.finish()
+        });
+
+        let compute_statistics = statistics.map(|statistics| {
+            let mut encoder = context
+                .device()
+                .create_command_encoder(&CommandEncoderDescriptor {
+                    label: Some("Simulation Statistics Command Encoder"),
+                });
+            statistics.encode(&mut encoder);
+            self.statistics_step.store(step, Ordering::Relaxed);
+            self.statistics_pending.store(true, Ordering::Relaxed);
+            encoder.finish()
+        });
+
+        let scope = ProfileScope::maybe(self.profiler.as_deref(), "Simulation Step");
+        let n_chunks = self.point_bind_groups.len();
+        let step_commands =
             self.point_bind_groups
                 .iter()
                 .enumerate()
@@ -249,12 +872,96 @@ impl<P: AsRef<Buffer<Point>>> Simulation<P> {
                     {
                         let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
                             label: Some(&format!("Simulation Compute Pass for Chunk #{idx}")),
-                            timestamp_writes: None,
+                            timestamp_writes: scope.compute_pass(idx, n_chunks),
                         });
 
                         compute_pass.set_pipeline(&self.pipeline);
                         compute_pass.set_bind_group(0, &self.map_bind_group, &[]);
                         compute_pass.set_bind_group(1, point_bind_group, &[]);
+                        compute_pass.set_bind_group(2, &self.escape_bind_group, &[]);
+                        compute_pass.dispatch_workgroups(len, 1, 1);
+                    }
+
+                    encoder.finish()
+                });
+
+        let commands = save_previous_points
+            .into_iter()
+            .chain(step_commands)
+            .chain(compute_statistics);
+
+        let submitted = context.queue().submit(commands);
+        scope.submitted(context);
+        submitted
+    }
+
+    /// Times each step with `profiler`, or stops timing if `None`.
+    pub fn set_profiler(&mut self, profiler: Option<Arc<Profiler>>) {
+        self.profiler = profiler;
+    }
+
+    /// Computes [`Statistics`] after every `interval` steps, or never if `None`.
+    ///
+    /// The points buffer must have [`BufferUsages::COPY_SRC`].
+    pub fn set_statistics_interval(&mut self, interval: Option<NonZero<usize>>, context: Context) {
+        self.statistics =
+            interval.map(|interval| StatisticsPass::new(interval, self.points.as_ref(), context));
+        self.statistics_pending.store(false, Ordering::Relaxed);
+    }
+
+    /// Downloads the statistics computed since the last call, if any.
+    pub fn take_statistics(
+        &self,
+        context: Context,
+    ) -> impl Future<Output = Result<Option<Statistics>>> + 'static {
+        let download = self
+            .statistics
+            .as_ref()
+            .filter(|_| self.statistics_pending.swap(false, Ordering::Relaxed))
+            .map(|statistics| statistics.result.download(context));
+        let step = self.statistics_step.load(Ordering::Relaxed);
+
+        async move {
+            Ok(match download {
+                Some(download) => Some(download.await?[0].finish(step)),
+                None => None,
+            })
+        }
+    }
+
+    /// Applies `transform` to every point, e.g. to move them to another frame of reference.
+    pub fn transform_points(&self, transform: Affine2, context: Context) -> impl SyncingFuture {
+        self.transform_buffer.write(
+            0,
+            &[WgpuMat3x3::from(Mat3::from(transform))],
+            context.borrow(),
+        );
+
+        let commands =
+            self.point_bind_groups
+                .iter()
+                .enumerate()
+                .map(|(idx, &(ref point_bind_group, len))| {
+                    let mut encoder =
+                        context
+                            .device()
+                            .create_command_encoder(&CommandEncoderDescriptor {
+                                label: Some(&format!(
+                                    "Simulation Point Transform Command Encoder for Chunk #{idx}"
+                                )),
+                            });
+
+                    {
+                        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                            label: Some(&format!(
+                                "Simulation Point Transform Compute Pass for Chunk #{idx}"
+                            )),
+                            timestamp_writes: None,
+                        });
+
+                        compute_pass.set_pipeline(&self.transform_pipeline);
+                        compute_pass.set_bind_group(0, &self.transform_bind_group, &[]);
+                        compute_pass.set_bind_group(1, point_bind_group, &[]);
                         compute_pass.dispatch_workgroups(len, 1, 1);
                     }
 
@@ -264,7 +971,156 @@ impl<P: AsRef<Buffer<Point>>> Simulation<P> {
         context.queue().submit(commands)
     }
 
+    /// Scatters the points uniformly over the escape region, e.g. to watch them converge again.
+    pub fn reset_points(&self, context: Context) -> impl SyncingFuture {
+        let mut encoder = context
+            .device()
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Simulation Reset Command Encoder"),
+            });
+        seed::encode_seed_points(
+            None,
+            self.points.as_ref(),
+            0,
+            &self.escape.region,
+            self.random_seed(self.step_count()),
+            &mut encoder,
+            context.borrow(),
+        );
+        context.queue().submit(iter::once(encoder.finish()))
+    }
+
     pub fn points(&self) -> &P {
         &self.points
     }
+
+    /// Downloads everything needed to resume the simulation later, see [`Simulation::load`].
+    ///
+    /// The points buffer must have [`BufferUsages::COPY_SRC`].
+    pub fn state(
+        &self,
+        camera: DAffine2,
+        context: Context,
+    ) -> impl Future<Output = Result<SimulationState>> + 'static {
+        let maps = self.maps.clone();
+        let escape = self.escape;
+        let precision = self.precision;
+        let step = self.step_count() as u64;
+        let seed = self.seed;
+        self.points.as_ref().download(context).map(move |points| {
+            Ok(SimulationState {
+                points: points?,
+                maps,
+                escape,
+                precision,
+                step,
+                seed,
+                camera,
+                frame: DAffine2::IDENTITY,
+            })
+        })
+    }
+}
+
+impl Simulation<Buffer<Point>> {
+    /// Uploads a state saved with [`Simulation::state`], in the local coordinates of its frame.
+    pub fn load(state: &SimulationState, context: Context) -> Self {
+        let points = Buffer::from_data(
+            &state.points,
+            Some("Points"),
+            BufferUsages::STORAGE | BufferUsages::VERTEX | BufferUsages::COPY_SRC,
+            context.borrow(),
+        );
+        let deep_zoom = DeepZoom::from_state(state);
+        let mut simulation = Self::with_precision(
+            points,
+            &deep_zoom.local_maps(),
+            state.precision,
+            context.borrow(),
+        );
+        simulation.step = AtomicUsize::new(state.step as usize);
+        simulation.seed = state.seed;
+        simulation.set_escape(deep_zoom.local_escape(), context);
+        simulation
+    }
+
+    /// Changes the number of points, keeping the first `len` existing ones.
+    ///
+    /// New points are seeded on top of random existing points so that they start out on the
+    /// attractor, or uniformly over the escape region if there were none. At least one point is
+    /// kept, as empty buffers can't be bound.
+    ///
+    /// The points buffer must have [`BufferUsages::COPY_SRC`].
+    pub fn resize(&mut self, len: usize, context: Context) -> impl SyncingFuture {
+        let len = len.max(1);
+        let points = Buffer::new(
+            len,
+            Some("Points"),
+            self.points.usage() | BufferUsages::COPY_DST,
+            context.borrow(),
+        );
+
+        let mut encoder = context
+            .device()
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Simulation Resize Command Encoder"),
+            });
+        let kept = self.points.len().min(len);
+        encoder.copy_buffer_to_buffer(
+            &self.points,
+            0,
+            &points,
+            0,
+            (kept * mem::size_of::<Point>()) as u64,
+        );
+        seed::encode_seed_points(
+            Some(&self.points),
+            &points,
+            kept,
+            &self.escape.region,
+            self.random_seed(self.step_count()),
+            &mut encoder,
+            context.borrow(),
+        );
+        let command = encoder.finish();
+
+        (self.point_bind_groups, self._chunk_offsets) =
+            Self::point_bind_groups(&self.point_bind_group_layout, &points, context.borrow());
+        self.points = points;
+
+        let statistics_interval = self
+            .statistics
+            .as_ref()
+            .map(|statistics| statistics.interval);
+        self.set_statistics_interval(statistics_interval, context.borrow());
+
+        context.queue().submit(iter::once(command))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_index_array_follows_weights() {
+        let indices = map_index_array(&[1.0, 2.0, 0.0]);
+        assert_eq!(indices.len(), 144);
+        assert_eq!(indices.iter().filter(|&&idx| idx == 0).count(), 48);
+        assert_eq!(indices.iter().filter(|&&idx| idx == 1).count(), 96);
+    }
+
+    #[test]
+    fn map_index_array_without_positive_weights() {
+        for weights in [
+            [0.0, 0.0],
+            [-1.0, 0.0],
+            [f32::NAN, 0.0],
+            [f32::INFINITY, 1.0],
+        ] {
+            let indices = map_index_array(&weights);
+            assert_eq!(indices.len(), 144, "{weights:?}");
+            assert_eq!(indices.iter().filter(|&&idx| idx == 0).count(), 72);
+        }
+    }
 }
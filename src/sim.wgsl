@@ -1,6 +1,4 @@
 @group(0) @binding(0) var<storage> maps: array<mat3x3<f32>>;
-@group(0) @binding(1) var<storage> map_indices: array<u32>;
-@group(1) @binding(0) var<storage, read_write> points: array<vec2<f32>>;
 
 @compute @workgroup_size(1) fn step_sim(
     @builtin(global_invocation_id) id: vec3<u32>,
@@ -8,9 +6,11 @@
     let point = points[id.x];
     let map_index = map_indices[hash(point) % arrayLength(&map_indices)];
     let map = maps[map_index];
-    points[id.x] = (map * vec3(point, 1.0)).xy;
-}
-
-fn hash(point: vec2<f32>) -> u32 {
-    return bitcast<u32>(point.x + point.y);
+    let next = (map * vec3(point, 1.0)).xy;
+    if has_escaped(next) {
+        points[id.x] = respawn(id.x);
+        atomicAdd(&respawn_count, 1u);
+    } else {
+        points[id.x] = next;
+    }
 }
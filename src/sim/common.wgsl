@@ -0,0 +1,66 @@
+// Shared by `sim.wgsl` and `sim/f64.wgsl`, which declare the maps and `step_sim`.
+// Requires `sim/random.wgsl`.
+
+struct Escape {
+    min: vec2<f32>,
+    max: vec2<f32>,
+    radius: f32,
+    respawn: u32,
+    seed: u32,
+}
+
+const RESPAWN_UNIFORM: u32 = 0u;
+const RESPAWN_LIVE_POINT: u32 = 1u;
+
+@group(0) @binding(1) var<storage> map_indices: array<u32>;
+struct Chunk {
+    // index of the chunk's first point in `all_points`
+    offset: u32,
+    // number of points in `all_points`
+    point_count: u32,
+}
+
+@group(1) @binding(0) var<storage, read_write> points: array<vec2<f32>>;
+@group(1) @binding(1) var<uniform> chunk: Chunk;
+// the points of all chunks, to respawn next to any live point
+@group(1) @binding(2) var<storage, read_write> all_points: array<vec2<f32>>;
+@group(2) @binding(0) var<uniform> escape: Escape;
+@group(2) @binding(1) var<storage, read_write> respawn_count: atomic<u32>;
+
+fn hash(point: vec2<f32>) -> u32 {
+    return bitcast<u32>(point.x + point.y);
+}
+
+fn has_escaped(point: vec2<f32>) -> bool {
+    let center = 0.5 * (escape.min + escape.max);
+    return !is_finite(point.x) || !is_finite(point.y) || distance(point, center) > escape.radius;
+}
+
+// `x != x` may be optimized away, so look at the exponent bits instead
+fn is_finite(x: f32) -> bool {
+    return (bitcast<u32>(x) & 0x7f800000u) != 0x7f800000u;
+}
+
+fn respawn(idx: u32) -> vec2<f32> {
+    var state = pcg(escape.seed ^ pcg(chunk.offset + idx));
+    let extent = escape.max - escape.min;
+
+    if escape.respawn == RESPAWN_LIVE_POINT {
+        state = pcg(state);
+        let other = all_points[state % chunk.point_count];
+        if !has_escaped(other) {
+            // jitter slightly, otherwise both points would follow the same orbit forever
+            state = pcg(state);
+            let x = to_unit_float(state);
+            state = pcg(state);
+            let y = to_unit_float(state);
+            return other + (vec2(x, y) - 0.5) * extent * 1.0e-6;
+        }
+    }
+
+    state = pcg(state);
+    let x = to_unit_float(state);
+    state = pcg(state);
+    let y = to_unit_float(state);
+    return escape.min + vec2(x, y) * extent;
+}
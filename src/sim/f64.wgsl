@@ -0,0 +1,16 @@
+@group(0) @binding(0) var<storage> maps: array<mat3x3<f64>>;
+
+@compute @workgroup_size(1) fn step_sim(
+    @builtin(global_invocation_id) id: vec3<u32>,
+) {
+    let point = points[id.x];
+    let map_index = map_indices[hash(point) % arrayLength(&map_indices)];
+    let map = maps[map_index];
+    let next = vec2<f32>((map * vec3(vec2<f64>(point), 1.0lf)).xy);
+    if has_escaped(next) {
+        points[id.x] = respawn(id.x);
+        atomicAdd(&respawn_count, 1u);
+    } else {
+        points[id.x] = next;
+    }
+}
@@ -0,0 +1,11 @@
+// https://www.reedbeta.com/blog/hash-functions-for-gpu-rendering/
+fn pcg(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// in range [0, 1)
+fn to_unit_float(value: u32) -> f32 {
+    return f32(value >> 8u) / 16777216.0;
+}
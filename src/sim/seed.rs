@@ -0,0 +1,164 @@
+use bytemuck::{Pod, Zeroable};
+use glam::Vec2;
+use wgpu::{
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+    BindingType, BufferBindingType, BufferUsages, CommandEncoder, ComputePassDescriptor,
+    ComputePipelineDescriptor, PipelineCompilationOptions, PipelineLayoutDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages,
+};
+
+use super::MAX_WORKGROUPS_PER_DIMENSION;
+use crate::{app::Context, buffer::Buffer, map::Rect, sim::Point};
+
+const WORKGROUP_SIZE: u32 = 256;
+
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C)]
+struct SeedUniform {
+    min: Vec2,
+    max: Vec2,
+    offset: u32,
+    len: u32,
+    n_sources: u32,
+    seed: u32,
+}
+
+/// Encodes seeding `points[offset..]` from random `sources`, jittered by a fraction of `region`,
+/// or uniformly over `region` if there are no `sources`.
+pub(super) fn encode_seed_points(
+    sources: Option<&Buffer<Point>>,
+    points: &Buffer<Point>,
+    offset: usize,
+    region: &Rect,
+    seed: u32,
+    encoder: &mut CommandEncoder,
+    context: Context,
+) {
+    let len = points.len_u32() - offset as u32;
+    if len == 0 {
+        return;
+    }
+
+    let sources = sources.filter(|sources| !sources.is_empty());
+    let n_sources = sources.map_or(0, Buffer::len_u32);
+    // empty buffers can't be bound
+    let placeholder;
+    let sources = match sources {
+        Some(sources) => sources,
+        None => {
+            placeholder = Buffer::new(
+                1,
+                Some("Simulation Seed Placeholder Sources"),
+                BufferUsages::STORAGE,
+                context.borrow(),
+            );
+            &placeholder
+        }
+    };
+
+    let parameters = Buffer::from_data(
+        &[SeedUniform {
+            min: region.min,
+            max: region.max,
+            offset: offset as u32,
+            len,
+            n_sources,
+            seed,
+        }],
+        Some("Simulation Seed Parameters"),
+        BufferUsages::UNIFORM,
+        context.borrow(),
+    );
+
+    let storage_entry = |binding, read_only| BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::COMPUTE,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    let bind_group_layout = context
+        .device()
+        .create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Simulation Seed Bind Group Layout"),
+            entries: &[
+                // sources
+                storage_entry(0, true),
+                // points
+                storage_entry(1, false),
+                // parameters
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+    let bind_group = context.device().create_bind_group(&BindGroupDescriptor {
+        label: Some("Simulation Seed Bind Group"),
+        layout: &bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: sources.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: points.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: parameters.as_entire_binding(),
+            },
+        ],
+    });
+
+    let pipeline_layout = context
+        .device()
+        .create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Simulation Seed Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+    let shader = context
+        .device()
+        .create_shader_module(ShaderModuleDescriptor {
+            label: Some("Simulation Seed Shader"),
+            source: ShaderSource::Wgsl(
+                concat!(include_str!("random.wgsl"), include_str!("seed.wgsl")).into(),
+            ),
+        });
+
+    let pipeline = context
+        .device()
+        .create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Simulation Seed Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("seed_points"),
+            compilation_options: PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+    let n_workgroups = len.div_ceil(WORKGROUP_SIZE);
+    let x = n_workgroups.min(MAX_WORKGROUPS_PER_DIMENSION);
+    let y = n_workgroups.div_ceil(x);
+
+    let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+        label: Some("Simulation Seed Compute Pass"),
+        timestamp_writes: None,
+    });
+    compute_pass.set_pipeline(&pipeline);
+    compute_pass.set_bind_group(0, &bind_group, &[]);
+    compute_pass.dispatch_workgroups(x, y, 1);
+}
@@ -0,0 +1,50 @@
+// Requires `random.wgsl`.
+
+struct Seed {
+    min: vec2<f32>,
+    max: vec2<f32>,
+    // index of the first point to seed
+    offset: u32,
+    // number of points to seed
+    len: u32,
+    // number of points to sample from `sources`, which may be a placeholder if there are none
+    n_sources: u32,
+    seed: u32,
+}
+
+const WORKGROUP_SIZE: u32 = 256u;
+
+@group(0) @binding(0) var<storage> sources: array<vec2<f32>>;
+@group(0) @binding(1) var<storage, read_write> points: array<vec2<f32>>;
+@group(0) @binding(2) var<uniform> parameters: Seed;
+
+// places each new point on top of a random source point, or uniformly over the region if there
+// are none
+@compute @workgroup_size(WORKGROUP_SIZE) fn seed_points(
+    @builtin(local_invocation_index) local_idx: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(num_workgroups) n_workgroups: vec3<u32>,
+) {
+    let idx = (workgroup_id.x + workgroup_id.y * n_workgroups.x) * WORKGROUP_SIZE + local_idx;
+    if idx >= parameters.len {
+        return;
+    }
+
+    var state = pcg(parameters.seed ^ pcg(idx));
+    let extent = parameters.max - parameters.min;
+
+    state = pcg(state);
+    let x = to_unit_float(state);
+    state = pcg(state);
+    let y = to_unit_float(state);
+    let unit = vec2(x, y);
+
+    let n_sources = parameters.n_sources;
+    if n_sources == 0u {
+        points[parameters.offset + idx] = parameters.min + unit * extent;
+    } else {
+        state = pcg(state);
+        // jitter slightly, otherwise both points would follow the same orbit forever
+        points[parameters.offset + idx] = sources[state % n_sources] + (unit - 0.5) * extent * 1.0e-6;
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    fmt::{self, Display},
+    num::NonZero,
+};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{dvec2, DMat2, Mat2, Vec2};
+use wgpu::{
+    include_wgsl, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, BufferBindingType, BufferUsages, CommandEncoder,
+    ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, PipelineCompilationOptions,
+    PipelineLayoutDescriptor, ShaderStages,
+};
+
+use super::MAX_WORKGROUPS_PER_DIMENSION;
+use crate::{app::Context, buffer::Buffer, map::Rect, sim::Point};
+
+const WORKGROUP_SIZE: u32 = 256;
+
+/// Statistics over the points of a [`super::Simulation`], computed on the GPU.
+#[derive(Debug, Clone, Copy)]
+pub struct Statistics {
+    /// Step after which the statistics were computed.
+    pub step: usize,
+    pub bounding_box: Rect,
+    pub mean: Vec2,
+    pub covariance: Mat2,
+    /// Mean squared distance travelled by the points during the step, a measure of convergence.
+    pub mean_squared_displacement: f32,
+    pub finite_count: u32,
+    pub non_finite_count: u32,
+}
+
+impl Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Rect { min, max } = self.bounding_box;
+        write!(
+            f,
+            "step {}: bounds [{:.4}, {:.4}]×[{:.4}, {:.4}], mean ({:.4}, {:.4}), \
+             variance ({:.4}, {:.4}), covariance {:.4}, mean squared displacement {:.3e}, \
+             {} non-finite points",
+            self.step,
+            min.x,
+            max.x,
+            min.y,
+            max.y,
+            self.mean.x,
+            self.mean.y,
+            self.covariance.x_axis.x,
+            self.covariance.y_axis.y,
+            self.covariance.y_axis.x,
+            self.mean_squared_displacement,
+            self.non_finite_count,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C)]
+pub(super) struct StatisticsGpu {
+    min: Vec2,
+    max: Vec2,
+    mean: Vec2,
+    // sums of products of deviations from `mean`
+    m2_xx: f32,
+    m2_xy: f32,
+    m2_yy: f32,
+    sum_squared_displacement: f32,
+    finite: u32,
+    non_finite: u32,
+}
+
+impl StatisticsGpu {
+    pub(super) fn finish(self, step: usize) -> Statistics {
+        let count = f64::from(self.finite.max(1));
+        let covariance = DMat2::from_cols(
+            dvec2(f64::from(self.m2_xx), f64::from(self.m2_xy)),
+            dvec2(f64::from(self.m2_xy), f64::from(self.m2_yy)),
+        ) * count.recip();
+        Statistics {
+            step,
+            bounding_box: Rect {
+                min: self.min,
+                max: self.max,
+            },
+            mean: self.mean,
+            covariance: covariance.as_mat2(),
+            mean_squared_displacement: (f64::from(self.sum_squared_displacement) / count) as f32,
+            finite_count: self.finite,
+            non_finite_count: self.non_finite,
+        }
+    }
+}
+
+/// GPU resources for computing [`Statistics`] every `interval` steps.
+#[derive(Debug)]
+pub(super) struct StatisticsPass {
+    pub(super) interval: NonZero<usize>,
+    pub(super) previous_points: Buffer<Point>,
+    pub(super) result: Buffer<StatisticsGpu>,
+    _partials: Buffer<StatisticsGpu>,
+    bind_group: BindGroup,
+    reduce_points_pipeline: ComputePipeline,
+    reduce_partials_pipeline: ComputePipeline,
+    n_workgroups: u32,
+}
+
+impl StatisticsPass {
+    pub(super) fn new(interval: NonZero<usize>, points: &Buffer<Point>, context: Context) -> Self {
+        let n_workgroups = points.len_u32().div_ceil(WORKGROUP_SIZE).max(1);
+
+        let previous_points = Buffer::new(
+            points.len(),
+            Some("Simulation Statistics Previous Points"),
+            BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            context.borrow(),
+        );
+        let partials = Buffer::new(
+            n_workgroups as usize,
+            Some("Simulation Statistics Partials"),
+            BufferUsages::STORAGE,
+            context.borrow(),
+        );
+        let result = Buffer::new(
+            1,
+            Some("Simulation Statistics Result"),
+            BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            context.borrow(),
+        );
+
+        let storage_entry = |binding, read_only| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout =
+            context
+                .device()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("Simulation Statistics Bind Group Layout"),
+                    entries: &[
+                        // points
+                        storage_entry(0, true),
+                        // previous points
+                        storage_entry(1, true),
+                        // partials
+                        storage_entry(2, false),
+                        // result
+                        storage_entry(3, false),
+                    ],
+                });
+
+        let bind_group = context.device().create_bind_group(&BindGroupDescriptor {
+            label: Some("Simulation Statistics Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: points.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: previous_points.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: partials.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: result.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = context
+            .device()
+            .create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Simulation Statistics Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let shader = context
+            .device()
+            .create_shader_module(include_wgsl!("statistics.wgsl"));
+
+        let pipeline = |label, entry_point| {
+            context
+                .device()
+                .create_compute_pipeline(&ComputePipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&pipeline_layout),
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: PipelineCompilationOptions::default(),
+                    cache: None,
+                })
+        };
+        let reduce_points_pipeline = pipeline(
+            "Simulation Statistics Point Reduction Pipeline",
+            "reduce_points",
+        );
+        let reduce_partials_pipeline = pipeline(
+            "Simulation Statistics Partial Reduction Pipeline",
+            "reduce_partials",
+        );
+
+        Self {
+            interval,
+            previous_points,
+            result,
+            _partials: partials,
+            bind_group,
+            reduce_points_pipeline,
+            reduce_partials_pipeline,
+            n_workgroups,
+        }
+    }
+
+    pub(super) fn encode(&self, encoder: &mut CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("Simulation Statistics Compute Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+
+        let x = self.n_workgroups.min(MAX_WORKGROUPS_PER_DIMENSION);
+        let y = self.n_workgroups.div_ceil(x);
+        compute_pass.set_pipeline(&self.reduce_points_pipeline);
+        compute_pass.dispatch_workgroups(x, y, 1);
+
+        compute_pass.set_pipeline(&self.reduce_partials_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+}
@@ -0,0 +1,125 @@
+// `m2_*` are sums of products of deviations from `mean`, merged following Chan et al. so that
+// points far from the origin don't cancel out their variance
+struct Statistics {
+    min: vec2<f32>,
+    max: vec2<f32>,
+    mean: vec2<f32>,
+    m2_xx: f32,
+    m2_xy: f32,
+    m2_yy: f32,
+    sum_squared_displacement: f32,
+    finite: u32,
+    non_finite: u32,
+}
+
+const WORKGROUP_SIZE: u32 = 256u;
+const F32_MAX: f32 = 3.40282347e38;
+
+@group(0) @binding(0) var<storage> points: array<vec2<f32>>;
+@group(0) @binding(1) var<storage> previous_points: array<vec2<f32>>;
+@group(0) @binding(2) var<storage, read_write> partials: array<Statistics>;
+@group(0) @binding(3) var<storage, read_write> result: Statistics;
+
+var<workgroup> workgroup_statistics: array<Statistics, WORKGROUP_SIZE>;
+
+// reduces the points of each workgroup into `partials`
+@compute @workgroup_size(WORKGROUP_SIZE) fn reduce_points(
+    @builtin(local_invocation_index) local_idx: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(num_workgroups) n_workgroups: vec3<u32>,
+) {
+    let group_idx = workgroup_id.x + workgroup_id.y * n_workgroups.x;
+    let statistics = reduce_workgroup(local_idx, point_statistics(group_idx * WORKGROUP_SIZE + local_idx));
+    if local_idx == 0u && group_idx < arrayLength(&partials) {
+        partials[group_idx] = statistics;
+    }
+}
+
+// reduces `partials` into `result`, in a single workgroup
+@compute @workgroup_size(WORKGROUP_SIZE) fn reduce_partials(
+    @builtin(local_invocation_index) local_idx: u32,
+) {
+    var statistics = empty();
+    for (var idx = local_idx; idx < arrayLength(&partials); idx += WORKGROUP_SIZE) {
+        statistics = merge(statistics, partials[idx]);
+    }
+    let total = reduce_workgroup(local_idx, statistics);
+    if local_idx == 0u {
+        result = total;
+    }
+}
+
+fn reduce_workgroup(local_idx: u32, statistics: Statistics) -> Statistics {
+    workgroup_statistics[local_idx] = statistics;
+    workgroupBarrier();
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {
+        if local_idx < stride {
+            workgroup_statistics[local_idx] = merge(
+                workgroup_statistics[local_idx],
+                workgroup_statistics[local_idx + stride],
+            );
+        }
+        workgroupBarrier();
+    }
+    return workgroup_statistics[0];
+}
+
+fn point_statistics(idx: u32) -> Statistics {
+    var statistics = empty();
+    if idx >= arrayLength(&points) {
+        return statistics;
+    }
+
+    let point = points[idx];
+    if !is_finite(point) {
+        statistics.non_finite = 1u;
+        return statistics;
+    }
+
+    statistics.min = point;
+    statistics.max = point;
+    statistics.mean = point;
+    statistics.finite = 1u;
+
+    let previous_point = previous_points[idx];
+    if is_finite(previous_point) {
+        let displacement = point - previous_point;
+        statistics.sum_squared_displacement = dot(displacement, displacement);
+    }
+
+    return statistics;
+}
+
+fn empty() -> Statistics {
+    return Statistics(vec2(F32_MAX), vec2(-F32_MAX), vec2(0.0), 0.0, 0.0, 0.0, 0.0, 0u, 0u);
+}
+
+fn merge(a: Statistics, b: Statistics) -> Statistics {
+    let finite = a.finite + b.finite;
+    var mean = vec2(0.0);
+    var m2 = vec3(a.m2_xx + b.m2_xx, a.m2_xy + b.m2_xy, a.m2_yy + b.m2_yy);
+    if finite > 0u {
+        let weight_b = f32(b.finite) / f32(finite);
+        let delta = b.mean - a.mean;
+        mean = a.mean + delta * weight_b;
+        let delta_products = vec3(delta.x * delta.x, delta.x * delta.y, delta.y * delta.y);
+        m2 += delta_products * f32(a.finite) * weight_b;
+    }
+    return Statistics(
+        min(a.min, b.min),
+        max(a.max, b.max),
+        mean,
+        m2.x,
+        m2.y,
+        m2.z,
+        a.sum_squared_displacement + b.sum_squared_displacement,
+        finite,
+        a.non_finite + b.non_finite,
+    );
+}
+
+// `x != x` may be optimized away, so look at the exponent bits instead
+fn is_finite(point: vec2<f32>) -> bool {
+    let exponents = bitcast<vec2<u32>>(point) & vec2(0x7f800000u);
+    return all(exponents != vec2(0x7f800000u));
+}
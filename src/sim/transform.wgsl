@@ -0,0 +1,8 @@
+@group(0) @binding(0) var<uniform> transform: mat3x3<f32>;
+@group(1) @binding(0) var<storage, read_write> points: array<vec2<f32>>;
+
+@compute @workgroup_size(1) fn transform_points(
+    @builtin(global_invocation_id) id: vec3<u32>,
+) {
+    points[id.x] = (transform * vec3(points[id.x], 1.0)).xy;
+}
@@ -0,0 +1,303 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    mem,
+    path::Path,
+};
+
+use color_eyre::eyre::{Result, WrapErr};
+use glam::{vec2, DAffine2};
+use thiserror::Error;
+
+use crate::{
+    map::{DMap, Rect},
+    sim::{Escape, Point, Precision, Respawn},
+};
+
+/// Everything needed to resume a [`crate::sim::Simulation`], see
+/// [`crate::sim::Simulation::state`] and [`crate::sim::Simulation::load`].
+///
+/// There is no per-point random state to save: the respawn randomness is derived from the seed,
+/// the step count and the point indices.
+///
+/// States saved while deep zooming (see [`crate::zoom::DeepZoom`]) have the points and camera in
+/// local coordinates, but the maps and escape in world coordinates.
+#[derive(Debug, Clone)]
+pub struct SimulationState {
+    pub points: Vec<Point>,
+    pub maps: Vec<DMap>,
+    pub escape: Escape,
+    pub precision: Precision,
+    pub step: u64,
+    pub seed: u32,
+    /// Transform from clip space to simulation coordinates.
+    pub camera: DAffine2,
+    /// Transform from the local coordinates of the points and camera to world coordinates.
+    pub frame: DAffine2,
+}
+
+#[derive(Debug, Clone, Copy, Error)]
+pub enum InvalidState {
+    #[error("not a nephos simulation state")]
+    Magic,
+    #[error("unsupported simulation state version {0}")]
+    Version(u32),
+    #[error("simulation state has points of {0} bytes, expected {expected}", expected = mem::size_of::<Point>())]
+    PointSize(u32),
+    #[error("simulation state has too many points ({0})")]
+    PointCount(u64),
+    #[error("invalid precision {0} in simulation state")]
+    Precision(u32),
+    #[error("invalid respawn mode {0} in simulation state")]
+    Respawn(u32),
+}
+
+impl SimulationState {
+    const MAGIC: [u8; 8] = *b"NEPHOSIM";
+    const VERSION: u32 = 3;
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut writer = BufWriter::new(
+            File::create(path).wrap_err_with(|| format!("failed to create {}", path.display()))?,
+        );
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut reader = BufReader::new(
+            File::open(path).wrap_err_with(|| format!("failed to open {}", path.display()))?,
+        );
+        Self::read(&mut reader)
+            .wrap_err_with(|| format!("failed to read simulation state from {}", path.display()))
+    }
+
+    /// Writes the state in a compact little-endian binary format.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&Self::MAGIC)?;
+        write_u32(writer, Self::VERSION)?;
+
+        write_u32(writer, self.precision as u32)?;
+        write_u64(writer, self.step)?;
+        write_u32(writer, self.seed)?;
+        write_affine(writer, self.camera)?;
+        write_affine(writer, self.frame)?;
+
+        let Escape {
+            region: Rect { min, max },
+            radius,
+            respawn,
+        } = self.escape;
+        for value in [min.x, min.y, max.x, max.y, radius] {
+            write_f32(writer, value)?;
+        }
+        write_u32(writer, respawn as u32)?;
+
+        write_u32(writer, self.maps.len() as u32)?;
+        for map in &self.maps {
+            write_affine(writer, map.map)?;
+            write_f32(writer, map.probability_weight)?;
+        }
+
+        write_u32(writer, mem::size_of::<Point>() as u32)?;
+        write_u64(writer, self.points.len() as u64)?;
+        for &word in bytemuck::cast_slice::<Point, u32>(&self.points) {
+            write_u32(writer, word)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read(reader: &mut impl Read) -> Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != Self::MAGIC {
+            return Err(InvalidState::Magic.into());
+        }
+        let version = read_u32(reader)?;
+        // version 1 had no seed, version 2 no frame
+        if !(1..=Self::VERSION).contains(&version) {
+            return Err(InvalidState::Version(version).into());
+        }
+
+        let precision = match read_u32(reader)? {
+            0 => Precision::Single,
+            1 => Precision::Double,
+            other => return Err(InvalidState::Precision(other).into()),
+        };
+        let step = read_u64(reader)?;
+        let seed = if version >= 2 { read_u32(reader)? } else { 0 };
+        let camera = read_affine(reader)?;
+        let frame = if version >= 3 {
+            read_affine(reader)?
+        } else {
+            DAffine2::IDENTITY
+        };
+
+        let min = vec2(read_f32(reader)?, read_f32(reader)?);
+        let max = vec2(read_f32(reader)?, read_f32(reader)?);
+        let radius = read_f32(reader)?;
+        let respawn = match read_u32(reader)? {
+            0 => Respawn::Uniform,
+            1 => Respawn::LivePoint,
+            other => return Err(InvalidState::Respawn(other).into()),
+        };
+        let escape = Escape {
+            region: Rect { min, max },
+            radius,
+            respawn,
+        };
+
+        let n_maps = read_u32(reader)?;
+        let maps = (0..n_maps)
+            .map(|_| {
+                Ok(DMap {
+                    map: read_affine(reader)?,
+                    probability_weight: read_f32(reader)?,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let point_size = read_u32(reader)?;
+        if point_size as usize != mem::size_of::<Point>() {
+            return Err(InvalidState::PointSize(point_size).into());
+        }
+        let n_points = read_u64(reader)?;
+        let len = usize::try_from(n_points)
+            .ok()
+            .and_then(|n_points| n_points.checked_mul(mem::size_of::<Point>()))
+            .ok_or(InvalidState::PointCount(n_points))?;
+        let mut bytes = vec![0; len];
+        reader.read_exact(&mut bytes)?;
+        let words: Vec<_> = bytes
+            .chunks_exact(mem::size_of::<u32>())
+            .map(|word| u32::from_le_bytes(word.try_into().expect("chunks are 4 bytes")))
+            .collect();
+        let points = bytemuck::cast_slice(&words).to_vec();
+
+        Ok(Self {
+            points,
+            maps,
+            escape,
+            precision,
+            step,
+            seed,
+            camera,
+            frame,
+        })
+    }
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_u64(writer: &mut impl Write, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_f32(writer: &mut impl Write, value: f32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_affine(writer: &mut impl Write, affine: DAffine2) -> io::Result<()> {
+    for value in affine.to_cols_array() {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    Ok(f32::from_bits(read_u32(reader)?))
+}
+
+fn read_affine(reader: &mut impl Read) -> io::Result<DAffine2> {
+    let mut values = [0.0; 6];
+    for value in &mut values {
+        *value = f64::from_bits(read_u64(reader)?);
+    }
+    Ok(DAffine2::from_cols_array(&values))
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{dvec2, vec2};
+
+    use super::*;
+
+    fn state() -> SimulationState {
+        let region = Rect {
+            min: vec2(-1.0, -1.0),
+            max: vec2(1.0, 1.0),
+        };
+        SimulationState {
+            points: vec![
+                Point {
+                    position: vec2(0.25, -0.5),
+                },
+                Point {
+                    position: vec2(1.0e-7, 3.0),
+                },
+            ],
+            maps: vec![DMap {
+                map: DAffine2::from_scale(dvec2(0.5, 0.5)),
+                probability_weight: 2.0,
+            }],
+            escape: Escape::new(region),
+            precision: Precision::Double,
+            step: 42,
+            seed: 7,
+            camera: DAffine2::from_translation(dvec2(0.1, 0.2)),
+            frame: DAffine2::from_scale(dvec2(1.0e-9, 1.0e-9)),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let state = state();
+        let mut bytes = Vec::new();
+        state.write(&mut bytes).unwrap();
+        let read = SimulationState::read(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(&read.points),
+            bytemuck::cast_slice::<_, u8>(&state.points),
+        );
+        assert_eq!(read.maps[0].map, state.maps[0].map);
+        assert_eq!(read.escape.region.max, state.escape.region.max);
+        assert_eq!(read.precision, state.precision);
+        assert_eq!((read.step, read.seed), (state.step, state.seed));
+        assert_eq!(read.camera, state.camera);
+        assert_eq!(read.frame, state.frame);
+    }
+
+    #[test]
+    fn rejects_too_many_points() {
+        let mut bytes = Vec::new();
+        state().write(&mut bytes).unwrap();
+        // the point count follows the point size, before the points themselves
+        let count_offset = bytes.len() - 2 * mem::size_of::<Point>() - mem::size_of::<u64>();
+        bytes[count_offset..count_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        let error = SimulationState::read(&mut bytes.as_slice()).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(InvalidState::PointCount(u64::MAX))
+        ));
+    }
+}
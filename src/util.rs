@@ -1,7 +1,7 @@
 use std::future::Future;
 
 use bytemuck::{Pod, Zeroable};
-use glam::{Affine2, Mat2, Mat3, Vec2, Vec4};
+use glam::{Affine2, DMat3, DVec4, Mat2, Mat3, Vec2, Vec4};
 use wgpu_async::WgpuFuture;
 
 // matrix of the form
@@ -32,6 +32,20 @@ impl From<WgpuMat3x3> for Mat3 {
     }
 }
 
+#[derive(Debug, Clone, Copy, Zeroable, Pod)]
+#[repr(C)]
+pub struct WgpuDMat3x3([DVec4; 3]);
+
+impl From<DMat3> for WgpuDMat3x3 {
+    fn from(mat: DMat3) -> Self {
+        WgpuDMat3x3([
+            mat.col(0).extend(0.0),
+            mat.col(1).extend(0.0),
+            mat.col(2).extend(0.0),
+        ])
+    }
+}
+
 /// A future that doesn't do any work upon polling, but rather serves to signal that a computation is done.
 pub trait SyncingFuture: Future<Output = ()> + 'static {
     fn ignore(self);
@@ -0,0 +1,124 @@
+use glam::{vec2, Affine2, DAffine2, DMat2, DVec2, Vec2};
+
+use crate::{
+    map::{DMap, Map, Rect},
+    sim::Escape,
+    state::SimulationState,
+};
+
+/// Deep zoom by recentring: the simulation runs in a local frame of reference, the clip space of
+/// a camera, so that `f32` precision is concentrated where we look.
+///
+/// The maps are conjugated by the frame's transform on the CPU, in double precision. Combine with
+/// [`crate::sim::Precision::Double`] to also apply them in double precision on the GPU.
+#[derive(Debug, Clone)]
+pub struct DeepZoom {
+    maps: Vec<DMap>,
+    escape: Escape,
+    /// Transform from local to world coordinates.
+    frame: DAffine2,
+}
+
+impl DeepZoom {
+    pub fn new(maps: &[Map], escape: Escape) -> Self {
+        Self {
+            maps: maps.iter().copied().map(DMap::from).collect(),
+            escape,
+            frame: DAffine2::IDENTITY,
+        }
+    }
+
+    /// Resumes deep zooming into a saved state.
+    pub fn from_state(state: &SimulationState) -> Self {
+        Self {
+            maps: state.maps.clone(),
+            escape: state.escape,
+            frame: state.frame,
+        }
+    }
+
+    pub fn frame(&self) -> DAffine2 {
+        self.frame
+    }
+
+    pub fn maps(&self) -> &[DMap] {
+        &self.maps
+    }
+
+    pub fn set_maps(&mut self, maps: &[DMap]) {
+        self.maps = maps.to_vec();
+    }
+
+    pub fn escape(&self) -> &Escape {
+        &self.escape
+    }
+
+    pub fn set_escape(&mut self, escape: Escape) {
+        self.escape = escape;
+    }
+
+    /// The maps, conjugated to act on local coordinates.
+    pub fn local_maps(&self) -> Vec<DMap> {
+        let inverse_frame = self.frame.inverse();
+        self.maps
+            .iter()
+            .map(|map| DMap {
+                map: inverse_frame * map.map * self.frame,
+                probability_weight: map.probability_weight,
+            })
+            .collect()
+    }
+
+    /// The escape parameters in local coordinates.
+    ///
+    /// The escape disc becomes an ellipse in local coordinates, so it is replaced by the smallest
+    /// disc containing it.
+    pub fn local_escape(&self) -> Escape {
+        let inverse_frame = self.frame.inverse();
+        let Rect { min, max } = self.escape.region;
+        let corners = [min, vec2(min.x, max.y), max, vec2(max.x, min.y)]
+            .map(|corner| inverse_frame.transform_point2(corner.as_dvec2()));
+        let local_min = corners.into_iter().fold(DVec2::INFINITY, DVec2::min);
+        let local_max = corners.into_iter().fold(DVec2::NEG_INFINITY, DVec2::max);
+
+        Escape {
+            region: Rect {
+                min: local_min.as_vec2(),
+                max: local_max.as_vec2(),
+            },
+            radius: (f64::from(self.escape.radius) * spectral_norm(inverse_frame.matrix2)) as f32,
+            respawn: self.escape.respawn,
+        }
+    }
+
+    /// Transform from the camera's clip space to local coordinates, to render the simulation.
+    pub fn local_camera(&self, camera: DAffine2) -> Affine2 {
+        (self.frame.inverse() * camera).as_affine2()
+    }
+
+    pub fn to_local(&self, world: DVec2) -> Vec2 {
+        self.frame.inverse().transform_point2(world).as_vec2()
+    }
+
+    pub fn to_world(&self, local: Vec2) -> DVec2 {
+        self.frame.transform_point2(local.as_dvec2())
+    }
+
+    /// Moves the local frame onto `camera`, which maps clip space to world coordinates.
+    ///
+    /// Returns the transform from the previous local coordinates to the new ones, which must be
+    /// applied to the points (e.g. with [`crate::sim::Simulation::transform_points`]) before
+    /// uploading [`DeepZoom::local_maps`] and [`DeepZoom::local_escape`].
+    pub fn recentre(&mut self, camera: DAffine2) -> Affine2 {
+        let previous_to_new = camera.inverse() * self.frame;
+        self.frame = camera;
+        previous_to_new.as_affine2()
+    }
+}
+
+// largest singular value of a 2×2 matrix
+fn spectral_norm(mat: DMat2) -> f64 {
+    let squares = mat.x_axis.length_squared() + mat.y_axis.length_squared();
+    let det = mat.determinant();
+    (0.5 * (squares + (squares * squares - 4.0 * det * det).max(0.0).sqrt())).sqrt()
+}